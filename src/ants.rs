@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::rules::Ant;
+
+/// Ants in order of id (oldest first), with stable ids and constant-time removal.
+///
+/// Removed ants leave an empty slot, so slot indices stay valid until [`Ants::compact`] is
+/// called.
+#[derive(Default)]
+pub struct Ants {
+    slots: Vec<Option<Ant>>,
+    /// Slot index of each ant, by id.
+    indices: HashMap<usize, usize>,
+    next_id: usize,
+}
+
+impl Ants {
+    /// Ants must be in order of id, and have ids below `next_id`.
+    pub fn restore(ants: Vec<Ant>, next_id: usize) -> Self {
+        let indices = ants
+            .iter()
+            .enumerate()
+            .map(|(slot, ant)| (ant.id, slot))
+            .collect();
+        Self {
+            slots: ants.into_iter().map(Some).collect(),
+            indices,
+            next_id,
+        }
+    }
+
+    pub fn next_id(&self) -> usize {
+        self.next_id
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.indices.contains_key(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Ant> {
+        let slot = *self.indices.get(&id)?;
+        self.slots[slot].as_mut()
+    }
+
+    /// Number of slots, including empty slots.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn slot(&self, slot: usize) -> Option<&Ant> {
+        self.slots[slot].as_ref()
+    }
+
+    pub fn slot_mut(&mut self, slot: usize) -> Option<&mut Ant> {
+        self.slots[slot].as_mut()
+    }
+
+    /// Assigns the next id to the ant, and returns it.
+    pub fn insert(&mut self, mut ant: Ant) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        ant.id = id;
+        self.indices.insert(id, self.slots.len());
+        self.slots.push(Some(ant));
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Ant> {
+        let slot = self.indices.remove(&id)?;
+        self.slots[slot].take()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ant> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Ant> {
+        self.slots.iter_mut().flatten()
+    }
+
+    /// Remove empty slots, if there are enough to be worthwhile. Invalidates slot indices.
+    pub fn compact(&mut self) {
+        if self.slots.len() < self.len() * 2 {
+            return;
+        }
+
+        self.slots.retain(Option::is_some);
+        for (slot, ant) in self.slots.iter().enumerate() {
+            let ant = ant.as_ref().unwrap();
+            self.indices.insert(ant.id, slot);
+        }
+    }
+}
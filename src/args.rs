@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use crate::text::parse_numeric;
+
+/// Command-line arguments.
+///
+/// Options override the corresponding schema properties.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: Command,
+    /// Schema file, or the journal, snapshot or log file for other commands.
+    pub filepath: String,
+    pub max_ticks: Option<u64>,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub max_changes: Option<u64>,
+    /// Read run control commands from stdin.
+    pub interactive: bool,
+    /// Breakpoint expressions, which imply `interactive`.
+    pub breakpoints: Vec<String>,
+    /// File to record the previous block of every write to.
+    pub journal: Option<String>,
+    /// File to save a snapshot to when the run stops or on request.
+    pub snapshot: Option<String>,
+    /// Also save a snapshot every this many ticks.
+    pub autosave: Option<u64>,
+    /// File to log every rule application and block write to.
+    pub record: Option<String>,
+    /// Playback speed, as a multiple of the recorded speed.
+    pub speed: Option<f64>,
+    /// Keep writes in an overlay instead of sending them to the server.
+    pub dry_run: bool,
+    /// File to export the dry run diff to, which implies `dry_run`.
+    pub diff: Option<String>,
+    /// Names of blocks which are never overwritten, in addition to the schema's.
+    pub protect: Vec<String>,
+    /// Regions which are never overwritten, in addition to the schema's, each in the form of the
+    /// `protect_region` property with commas between values.
+    pub protect_regions: Vec<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub connect_timeout: Option<Duration>,
+    /// Number of attempts to reconnect after a connection failure, or `0` to never reconnect.
+    pub reconnect: Option<u32>,
+    /// Use an in-memory world instead of connecting to a server.
+    pub offline: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub enum Command {
+    /// Run a schema.
+    #[default]
+    Run,
+    /// Restore the blocks recorded in a journal.
+    Undo,
+    /// Continue a run from a snapshot.
+    Resume,
+    /// Play back the block writes of a log.
+    Play,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, String> {
+        let mut args = std::env::args();
+        args.next();
+
+        let mut filepath = None;
+        let mut result = Args::default();
+
+        let mut args = args.peekable();
+        if let Some(command) =
+            args.next_if(|arg| matches!(arg.as_str(), "undo" | "resume" | "play"))
+        {
+            result.command = match command.as_str() {
+                "undo" => Command::Undo,
+                "resume" => Command::Resume,
+                _ => Command::Play,
+            };
+        }
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                if filepath.is_some() {
+                    return Err(format!("unexpected argument `{}`", arg));
+                }
+                filepath = Some(arg);
+                continue;
+            }
+
+            if arg == "--interactive" {
+                result.interactive = true;
+                continue;
+            }
+            if arg == "--dry-run" {
+                result.dry_run = true;
+                continue;
+            }
+            if arg == "--offline" {
+                result.offline = true;
+                continue;
+            }
+
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for option `{}`", arg))
+            };
+
+            match arg.as_str() {
+                "--max-ticks" => result.max_ticks = Some(parse_numeric(&value()?)?),
+                "--max-steps" => result.max_steps = Some(parse_numeric(&value()?)?),
+                "--timeout" => {
+                    result.timeout = Some(Duration::from_secs(parse_numeric(&value()?)?))
+                }
+                "--max-changes" => result.max_changes = Some(parse_numeric(&value()?)?),
+                "--journal" => result.journal = Some(value()?),
+                "--diff" => {
+                    result.diff = Some(value()?);
+                    result.dry_run = true;
+                }
+                "--protect" => {
+                    let value = value()?;
+                    result
+                        .protect
+                        .extend(value.split(',').map(|name| name.trim().to_string()));
+                }
+                "--protect-region" => result.protect_regions.push(value()?),
+                "--host" => result.host = Some(value()?),
+                "--port" => result.port = Some(parse_numeric(&value()?)?),
+                "--connect-timeout" => {
+                    result.connect_timeout = Some(Duration::from_secs(parse_nonzero(&value()?)?))
+                }
+                "--reconnect" => result.reconnect = Some(parse_numeric(&value()?)?),
+                "--record" => result.record = Some(value()?),
+                "--speed" => {
+                    let speed: f64 = parse_numeric(&value()?)?;
+                    if !(speed > 0.0 && speed.is_finite()) {
+                        return Err(String::from("speed must be greater than 0"));
+                    }
+                    result.speed = Some(speed);
+                }
+                "--snapshot" => result.snapshot = Some(value()?),
+                "--autosave" => result.autosave = Some(parse_nonzero(&value()?)?),
+                "--break" => {
+                    result.breakpoints.push(value()?);
+                    result.interactive = true;
+                }
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+        }
+
+        result.filepath = filepath.ok_or("missing filepath")?;
+        Ok(result)
+    }
+}
+
+fn parse_nonzero(string: &str) -> Result<u64, String> {
+    match parse_numeric(string)? {
+        0 => Err(String::from("value must be greater than 0")),
+        value => Ok(value),
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use mcrs::{Block, Coordinate};
+
+/// Where blocks are read from and written to.
+pub trait Backend {
+    fn get_player_position(&mut self) -> Result<Coordinate, mcrs::Error>;
+
+    fn get_block(&mut self, location: Coordinate) -> Result<Block, mcrs::Error>;
+
+    /// Every block in the cuboid between two corners, with its location.
+    fn get_blocks(
+        &mut self,
+        corner_a: Coordinate,
+        corner_b: Coordinate,
+    ) -> Result<Vec<(Coordinate, Block)>, mcrs::Error>;
+
+    fn set_block(&mut self, location: Coordinate, block: Block) -> Result<(), mcrs::Error>;
+
+    /// Set every block in the cuboid between two corners.
+    fn set_blocks(
+        &mut self,
+        corner_a: Coordinate,
+        corner_b: Coordinate,
+        block: Block,
+    ) -> Result<(), mcrs::Error>;
+
+    fn do_command(&mut self, command: fmt::Arguments) -> Result<(), mcrs::Error>;
+
+    /// Whether particle commands are shown, so ant indicators are worth drawing.
+    fn shows_particles(&self) -> bool {
+        true
+    }
+}
+
+impl Backend for mcrs::Connection {
+    fn get_player_position(&mut self) -> Result<Coordinate, mcrs::Error> {
+        self.get_player_position()
+    }
+
+    fn get_block(&mut self, location: Coordinate) -> Result<Block, mcrs::Error> {
+        self.get_block(location)
+    }
+
+    fn get_blocks(
+        &mut self,
+        corner_a: Coordinate,
+        corner_b: Coordinate,
+    ) -> Result<Vec<(Coordinate, Block)>, mcrs::Error> {
+        let chunk = self.get_blocks(corner_a, corner_b)?;
+        Ok((&chunk)
+            .into_iter()
+            .map(|entry| (entry.position_worldspace(), entry.block()))
+            .collect())
+    }
+
+    fn set_block(&mut self, location: Coordinate, block: Block) -> Result<(), mcrs::Error> {
+        self.set_block(location, block)
+    }
+
+    fn set_blocks(
+        &mut self,
+        corner_a: Coordinate,
+        corner_b: Coordinate,
+        block: Block,
+    ) -> Result<(), mcrs::Error> {
+        self.set_blocks(corner_a, corner_b, block)
+    }
+
+    fn do_command(&mut self, command: fmt::Arguments) -> Result<(), mcrs::Error> {
+        self.do_command(command)
+    }
+}
+
+/// Offline world, where every block is air until it is written.
+///
+/// The player is at the world origin, and commands are ignored.
+#[derive(Default)]
+pub struct Memory {
+    blocks: HashMap<Coordinate, Block>,
+}
+
+impl Backend for Memory {
+    fn get_player_position(&mut self) -> Result<Coordinate, mcrs::Error> {
+        Ok(Coordinate::new(0, 0, 0))
+    }
+
+    fn get_block(&mut self, location: Coordinate) -> Result<Block, mcrs::Error> {
+        Ok(self.blocks.get(&location).copied().unwrap_or(Block::AIR))
+    }
+
+    fn get_blocks(
+        &mut self,
+        corner_a: Coordinate,
+        corner_b: Coordinate,
+    ) -> Result<Vec<(Coordinate, Block)>, mcrs::Error> {
+        let (min, max) = (corner_a.min(corner_b), corner_a.max(corner_b));
+        let mut blocks = Vec::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let location = Coordinate::new(x, y, z);
+                    blocks.push((location, self.get_block(location)?));
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    fn set_block(&mut self, location: Coordinate, block: Block) -> Result<(), mcrs::Error> {
+        if block == Block::AIR {
+            self.blocks.remove(&location);
+        } else {
+            self.blocks.insert(location, block);
+        }
+        Ok(())
+    }
+
+    fn set_blocks(
+        &mut self,
+        corner_a: Coordinate,
+        corner_b: Coordinate,
+        block: Block,
+    ) -> Result<(), mcrs::Error> {
+        let (min, max) = (corner_a.min(corner_b), corner_a.max(corner_b));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    self.set_block(Coordinate::new(x, y, z), block)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn do_command(&mut self, _command: fmt::Arguments) -> Result<(), mcrs::Error> {
+        Ok(())
+    }
+
+    fn shows_particles(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use mcrs::{Block, Coordinate};
+
+/// Block writes which have not been sent to the server yet.
+///
+/// Later writes to a location replace earlier ones, and writes are sent as cuboids of identical
+/// blocks where possible.
+#[derive(Default)]
+pub struct WriteQueue {
+    writes: HashMap<Coordinate, Block>,
+}
+
+impl WriteQueue {
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    pub fn push(&mut self, location: Coordinate, block: Block) {
+        self.writes.insert(location, block);
+    }
+
+    /// The latest queued write to a location.
+    pub fn get(&self, location: Coordinate) -> Option<Block> {
+        self.writes.get(&location).copied()
+    }
+
+    /// Drop queued writes inside a cuboid, with corners in order.
+    pub fn remove_within(&mut self, min: Coordinate, max: Coordinate) {
+        self.writes
+            .retain(|location, _| location.min(min) != min || location.max(max) != max);
+    }
+
+    /// Empty the queue, returning cuboids which cover every write exactly, as minimum and maximum
+    /// corners and block.
+    pub fn take_cuboids(&mut self) -> Vec<(Coordinate, Coordinate, Block)> {
+        let mut by_block: HashMap<Block, Vec<Coordinate>> = HashMap::new();
+        for (location, block) in self.writes.drain() {
+            by_block.entry(block).or_default().push(location);
+        }
+
+        let mut cuboids = Vec::new();
+        for (block, mut locations) in by_block {
+            locations.sort_by_key(|location| (location.y, location.z, location.x));
+
+            // Runs along x
+            let mut rows: Vec<(Coordinate, Coordinate)> = Vec::new();
+            for location in locations {
+                match rows.last_mut() {
+                    Some((_, end))
+                        if end.y == location.y
+                            && end.z == location.z
+                            && end.x + 1 == location.x =>
+                    {
+                        *end = location;
+                    }
+                    _ => rows.push((location, location)),
+                }
+            }
+
+            // Rows with the same x range on adjacent z, in the same layer
+            let mut rectangles: Vec<(Coordinate, Coordinate)> = Vec::new();
+            // Index of each rectangle by layer, x range and last z
+            let mut open: HashMap<(i32, i32, i32, i32), usize> = HashMap::new();
+            for (start, end) in rows {
+                match open.remove(&(start.y, start.x, end.x, start.z - 1)) {
+                    Some(index) => {
+                        rectangles[index].1.z = end.z;
+                        open.insert((start.y, start.x, end.x, end.z), index);
+                    }
+                    None => {
+                        open.insert((start.y, start.x, end.x, end.z), rectangles.len());
+                        rectangles.push((start, end));
+                    }
+                }
+            }
+
+            cuboids.extend(rectangles.into_iter().map(|(min, max)| (min, max, block)));
+        }
+        cuboids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expand cuboids into single blocks, checking that they do not overlap.
+    fn cells(cuboids: &[(Coordinate, Coordinate, Block)]) -> HashMap<Coordinate, Block> {
+        let mut cells = HashMap::new();
+        for (min, max, block) in cuboids {
+            assert_eq!(min.min(*max), *min, "corners should be in order");
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    for z in min.z..=max.z {
+                        let previous = cells.insert(Coordinate::new(x, y, z), *block);
+                        assert_eq!(previous, None, "cuboids should not overlap");
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    fn queue(writes: &[([i32; 3], Block)]) -> WriteQueue {
+        let mut queue = WriteQueue::default();
+        for (location, block) in writes {
+            queue.push((*location).into(), *block);
+        }
+        queue
+    }
+
+    #[test]
+    fn cuboids_cover_writes_exactly() {
+        let mut writes = Vec::new();
+        for x in 0..4 {
+            for z in 0..3 {
+                writes.push(([x, 0, z], Block::STONE));
+            }
+        }
+        writes.push(([1, 1, 1], Block::STONE));
+        writes.push(([2, 0, 1], Block::DIRT));
+        writes.push(([-3, 5, 7], Block::GLASS));
+        let mut queue = queue(&writes);
+
+        let cuboids = queue.take_cuboids();
+        let expected: HashMap<Coordinate, Block> = (writes.iter())
+            .map(|(location, block)| (Coordinate::from(*location), *block))
+            .collect();
+        assert_eq!(cells(&cuboids), expected);
+        assert!(queue.is_empty());
+        assert!(queue.take_cuboids().is_empty());
+    }
+
+    #[test]
+    fn rectangle_is_one_cuboid() {
+        let mut writes = Vec::new();
+        for x in -2..3 {
+            for z in 4..7 {
+                writes.push(([x, 1, z], Block::STONE));
+            }
+        }
+        let cuboids = queue(&writes).take_cuboids();
+
+        assert_eq!(
+            cuboids,
+            [(
+                Coordinate::new(-2, 1, 4),
+                Coordinate::new(2, 1, 6),
+                Block::STONE
+            )]
+        );
+    }
+
+    #[test]
+    fn rows_of_different_lengths_are_not_merged() {
+        let writes = [
+            ([0, 0, 0], Block::STONE),
+            ([1, 0, 0], Block::STONE),
+            ([0, 0, 1], Block::STONE),
+        ];
+        let cuboids = queue(&writes).take_cuboids();
+
+        assert_eq!(cuboids.len(), 2);
+        assert_eq!(cells(&cuboids).len(), 3);
+    }
+
+    #[test]
+    fn later_writes_replace_earlier_writes() {
+        let mut queue = queue(&[([0, 0, 0], Block::STONE), ([0, 0, 0], Block::DIRT)]);
+        assert_eq!(queue.get(Coordinate::new(0, 0, 0)), Some(Block::DIRT));
+
+        let cuboids = queue.take_cuboids();
+        assert_eq!(
+            cuboids,
+            [(
+                Coordinate::new(0, 0, 0),
+                Coordinate::new(0, 0, 0),
+                Block::DIRT
+            )]
+        );
+    }
+
+    #[test]
+    fn remove_within_keeps_writes_outside() {
+        let mut queue = queue(&[
+            ([0, 0, 0], Block::STONE),
+            ([1, 0, 0], Block::STONE),
+            ([5, 0, 0], Block::STONE),
+        ]);
+        queue.remove_within(Coordinate::new(0, -1, -1), Coordinate::new(1, 1, 1));
+
+        assert_eq!(queue.get(Coordinate::new(0, 0, 0)), None);
+        assert_eq!(queue.get(Coordinate::new(5, 0, 0)), Some(Block::STONE));
+    }
+}
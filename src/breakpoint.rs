@@ -0,0 +1,118 @@
+use std::fmt;
+
+use mcrs::{Block, Coordinate};
+
+use crate::parse::Parser;
+use crate::rules::{Ant, Axis, Direction, State};
+use crate::text::parse_numeric;
+
+/// Condition which pauses the run before a rule is applied.
+///
+/// Written as space-separated `key=value` terms, which must all match. Eg. `ant=3 state=retreat
+/// facing=north` or `write=gold_block`.
+#[derive(Debug)]
+pub struct Breakpoint {
+    source: String,
+    terms: Vec<Term>,
+}
+
+#[derive(Debug)]
+enum Term {
+    Ant(usize),
+    Ruleset(String),
+    State(State),
+    Facing(Direction),
+    /// Block under the ant.
+    Block(Block),
+    /// Block written by the rule, including by actions.
+    Write(Block),
+    /// Index of the rule in its ruleset, starting at 1.
+    Rule(usize),
+    Axis(Axis, i32),
+    Position(Coordinate),
+}
+
+/// Information about a rule which is about to be applied.
+pub struct Context<'a> {
+    pub ant: &'a Ant,
+    pub block: Block,
+    pub rule_index: usize,
+    pub writes: &'a dyn Fn(Block) -> bool,
+}
+
+impl Breakpoint {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut terms = Vec::new();
+
+        for word in source.split_whitespace() {
+            let Some((key, value)) = word.split_once('=') else {
+                return Err(format!("expected `key=value`, found `{}`", word));
+            };
+
+            let term = match key {
+                "ant" => Term::Ant(parse_numeric(value)?),
+                "ruleset" => Term::Ruleset(value.to_string()),
+                "state" => Term::State(value.to_string()),
+                "facing" => Term::Facing(
+                    Parser::parse_direction(value)
+                        .ok_or_else(|| format!("unknown direction `{}`", value))?,
+                ),
+                "block" => Term::Block(parse_block(value)?),
+                "write" => Term::Write(parse_block(value)?),
+                "rule" => Term::Rule(parse_numeric(value)?),
+                "x" => Term::Axis(Axis::X, parse_numeric(value)?),
+                "y" => Term::Axis(Axis::Y, parse_numeric(value)?),
+                "z" => Term::Axis(Axis::Z, parse_numeric(value)?),
+                "pos" => {
+                    let mut parts = value.split(',');
+                    let (Some(x), Some(y), Some(z), None) =
+                        (parts.next(), parts.next(), parts.next(), parts.next())
+                    else {
+                        return Err(format!("expected `pos=x,y,z`, found `{}`", word));
+                    };
+                    Term::Position(Coordinate::new(
+                        parse_numeric(x)?,
+                        parse_numeric(y)?,
+                        parse_numeric(z)?,
+                    ))
+                }
+                _ => return Err(format!("unknown breakpoint key `{}`", key)),
+            };
+            terms.push(term);
+        }
+
+        if terms.is_empty() {
+            return Err(String::from("empty breakpoint"));
+        }
+
+        Ok(Self {
+            source: source.trim().to_string(),
+            terms,
+        })
+    }
+
+    pub fn matches(&self, context: &Context) -> bool {
+        let ant = context.ant;
+        self.terms.iter().all(|term| match term {
+            Term::Ant(id) => ant.id == *id,
+            Term::Ruleset(name) => ant.ruleset.eq_ignore_ascii_case(name),
+            Term::State(state) => ant.state == *state,
+            Term::Facing(facing) => ant.facing == *facing,
+            Term::Block(block) => context.block == *block,
+            Term::Write(block) => (context.writes)(*block),
+            Term::Rule(index) => context.rule_index + 1 == *index,
+            Term::Axis(axis, value) => axis.get(ant.position) == *value,
+            Term::Position(position) => ant.position == *position,
+        })
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn parse_block(string: &str) -> Result<Block, String> {
+    Parser::parse_block(string).ok_or_else(|| format!("unknown block `{}`", string))
+}
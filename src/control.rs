@@ -0,0 +1,232 @@
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::time::Duration;
+
+use crate::breakpoint::Breakpoint;
+use crate::engine::{Engine, Progress, Stop};
+
+const MIN_SLOWER_DELAY: Duration = Duration::from_millis(10);
+/// How often to return while paused, so that the run can be interrupted.
+const PAUSED_POLL: Duration = Duration::from_millis(100);
+
+const HELP: &str = "\
+commands:
+    p, pause        pause the run
+    r, resume       resume the run
+    s, step         step one tick
+    a, ant          step one ant
+    +, faster       halve the delay
+    -, slower       double the delay
+    h, halt <id>    halt an ant
+    b, break <expr> add a breakpoint, eg. `b ant=3 state=retreat facing=north`
+    d, delete <n>   remove a breakpoint
+    l, list         list breakpoints
+    w, save [file]  save a snapshot, between ticks
+    q, quit         stop the run";
+
+/// Interactive run control, with commands read from stdin.
+pub struct Control {
+    receiver: Receiver<String>,
+    /// Set when stdin is closed.
+    closed: bool,
+    paused: bool,
+    delay: Duration,
+    schema_path: String,
+    /// Default file for `save`.
+    snapshot_path: Option<String>,
+}
+
+enum Command {
+    Pause,
+    Resume,
+    StepTick,
+    StepAnt,
+    Faster,
+    Slower,
+    Halt(usize),
+    Break(String),
+    Delete(usize),
+    List,
+    Save(Option<String>),
+    Quit,
+}
+
+impl Control {
+    pub fn new(delay: Duration, schema_path: String, snapshot_path: Option<String>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        eprintln!("{}", HELP);
+
+        Self {
+            receiver,
+            closed: false,
+            paused: false,
+            delay,
+            schema_path,
+            snapshot_path,
+        }
+    }
+
+    /// Handle pending commands, then run a tick unless paused. Blocks while paused.
+    ///
+    /// Returns [`Stop::Quit`] if the user quit.
+    pub fn update(&mut self, engine: &mut Engine) -> Result<Option<Stop>, mcrs::Error> {
+        loop {
+            let line = if self.paused && !self.closed {
+                // Show writes from a partial tick while waiting
+                engine.flush()?;
+                match self.receiver.recv_timeout(PAUSED_POLL) {
+                    Ok(line) => line,
+                    Err(RecvTimeoutError::Timeout) => return Ok(None),
+                    Err(RecvTimeoutError::Disconnected) => {
+                        self.closed = true;
+                        self.paused = false;
+                        continue;
+                    }
+                }
+            } else {
+                match self.receiver.try_recv() {
+                    Ok(line) => line,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.closed = true;
+                        break;
+                    }
+                }
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+            let Some(command) = Self::parse_command(&line) else {
+                eprintln!("{}", HELP);
+                continue;
+            };
+
+            match command {
+                Command::Pause => {
+                    self.paused = true;
+                    println!("====[ PAUSED ]====");
+                }
+                Command::Resume => {
+                    self.paused = false;
+                    println!("====[ RESUMED ]====");
+                }
+                Command::StepTick => {
+                    self.paused = true;
+                    self.report(engine.tick()?);
+                }
+                Command::StepAnt => {
+                    self.paused = true;
+                    self.report(engine.step()?);
+                }
+                Command::Faster => {
+                    self.delay /= 2;
+                    println!("====[ DELAY {}ms ]====", self.delay.as_millis());
+                }
+                Command::Slower => {
+                    self.delay = (self.delay * 2).max(MIN_SLOWER_DELAY);
+                    println!("====[ DELAY {}ms ]====", self.delay.as_millis());
+                }
+                Command::Halt(id) => {
+                    if engine.halt(id) {
+                        println!("====[ HALTED {} ]====", id);
+                    } else {
+                        eprintln!("no live ant with id {}", id);
+                    }
+                }
+                Command::Break(source) => match Breakpoint::parse(&source) {
+                    Ok(breakpoint) => {
+                        engine.add_breakpoint(breakpoint);
+                        println!(
+                            "====[ BREAKPOINT {} ADDED ]====",
+                            engine.breakpoints().len()
+                        );
+                    }
+                    Err(error) => eprintln!("{}", error),
+                },
+                Command::Delete(number) => {
+                    match number
+                        .checked_sub(1)
+                        .and_then(|index| engine.remove_breakpoint(index))
+                    {
+                        Some(breakpoint) => println!("====[ DELETED {} ]====", breakpoint),
+                        None => eprintln!("no breakpoint {}", number),
+                    }
+                }
+                Command::List => {
+                    for (index, breakpoint) in engine.breakpoints().iter().enumerate() {
+                        println!("{:2}: {}", index + 1, breakpoint);
+                    }
+                }
+                Command::Save(path) => {
+                    let Some(path) = path.or_else(|| self.snapshot_path.clone()) else {
+                        eprintln!("no snapshot file given");
+                        continue;
+                    };
+                    let Some(snapshot) = engine.snapshot(&self.schema_path) else {
+                        eprintln!("tick in progress, step to the end of the tick first");
+                        continue;
+                    };
+                    match snapshot.save(&path) {
+                        Ok(()) => println!("====[ SAVED {} ]====", path),
+                        Err(error) => eprintln!("{}", error),
+                    }
+                }
+                Command::Quit => return Ok(Some(Stop::Quit)),
+            }
+
+            if engine.stopped().is_some() {
+                return Ok(None);
+            }
+        }
+
+        std::thread::sleep(self.delay);
+        let progress = engine.tick()?;
+        self.report(progress);
+        std::thread::sleep(self.delay);
+        Ok(None)
+    }
+
+    /// Pause if a breakpoint was hit.
+    fn report(&mut self, progress: Progress) {
+        if progress == Progress::Breakpoint {
+            self.paused = true;
+            println!("====[ PAUSED ]====");
+        }
+    }
+
+    fn parse_command(line: &str) -> Option<Command> {
+        let mut words = line.split_whitespace();
+        let command = match words.next()? {
+            "p" | "pause" => Command::Pause,
+            "r" | "resume" => Command::Resume,
+            "s" | "step" => Command::StepTick,
+            "a" | "ant" => Command::StepAnt,
+            "+" | "faster" => Command::Faster,
+            "-" | "slower" => Command::Slower,
+            "h" | "halt" => Command::Halt(words.next()?.parse().ok()?),
+            "b" | "break" => Command::Break(words.by_ref().collect::<Vec<_>>().join(" ")),
+            "d" | "delete" => Command::Delete(words.next()?.parse().ok()?),
+            "l" | "list" => Command::List,
+            "w" | "save" => Command::Save(words.next().map(String::from)),
+            "q" | "quit" => Command::Quit,
+            _ => return None,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(command)
+    }
+}
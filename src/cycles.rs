@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use mcrs::Coordinate;
+
+use crate::rules::{Ant, Direction, State};
+
+/// Detects ants which revisit an identical configuration without the world changing.
+#[derive(Default)]
+pub struct CycleDetector {
+    histories: HashMap<usize, History>,
+}
+
+struct History {
+    /// World change count when the history was started.
+    changes: u64,
+    /// Step count when each configuration was seen.
+    seen: HashMap<(Coordinate, State, Direction), u64>,
+    /// Whether a cycle has been reported since the world last changed.
+    reported: bool,
+}
+
+impl CycleDetector {
+    /// Histories are cleared when they grow past this, bounding memory for ants which wander
+    /// forever without changing anything.
+    const MAX_HISTORY: usize = 1024;
+
+    /// Record the configuration of an ant.
+    ///
+    /// Returns the cycle length in steps, if the configuration was seen before with no world
+    /// changes since. Each cycle is only reported once.
+    pub fn check(&mut self, ant: &Ant, changes: u64) -> Option<u64> {
+        let history = self.histories.entry(ant.id).or_insert_with(|| History {
+            changes,
+            seen: HashMap::new(),
+            reported: false,
+        });
+
+        if history.changes != changes || history.seen.len() >= Self::MAX_HISTORY {
+            history.changes = changes;
+            history.seen.clear();
+            history.reported = false;
+        }
+
+        let key = (ant.position, ant.state.clone(), ant.facing);
+        if let Some(previous) = history.seen.insert(key, ant.steps)
+            && !history.reported
+        {
+            history.reported = true;
+            return Some(ant.steps - previous);
+        }
+        None
+    }
+
+    /// Forget the configurations of an ant, when it is waiting for something other than a world
+    /// change.
+    pub fn reset(&mut self, id: usize) {
+        self.histories.remove(&id);
+    }
+
+    /// Remove histories of ants which no longer exist.
+    pub fn retain(&mut self, mut exists: impl FnMut(usize) -> bool) {
+        self.histories.retain(|id, _| exists(*id));
+    }
+}
@@ -0,0 +1,1593 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+use std::time::Instant;
+
+use mcrs::{Block, Coordinate};
+
+use crate::ants::Ants;
+use crate::breakpoint::{self, Breakpoint};
+use crate::cycles::CycleDetector;
+use crate::field::Field;
+use crate::indicator::show_ant_indicator;
+use crate::protect::Protection;
+use crate::rules::{
+    Action, Ant, Axis, CapPolicy, Collision, Condition, Cycles, Direction, Halt, ProtectPolicy,
+    Quantity, RegionPolicy, Relative, Rule, Ruleset, Schema, State, Template, Update,
+};
+use crate::snapshot::{self, Snapshot};
+use crate::world::World;
+
+const DEAFULT_CAP: usize = 50;
+const DEFAULT_DECAY: f64 = 0.0;
+const DEFAULT_DIFFUSION: f64 = 0.0;
+const DEFAULT_PREFETCH: u32 = 4;
+
+pub struct Engine {
+    /// Shared so that rules can be borrowed while the engine is mutated.
+    schema: Rc<Schema>,
+    world: World,
+    ants: Ants,
+    shared: Shared,
+    /// Rulesets which can be signalled, so halted ants using them must not be retired.
+    signalled: Vec<String>,
+    /// Rulesets whose ants are not checked for cycles, because they can wait for something other
+    /// than a world change, such as a global, a scent, another ant or a number of ticks.
+    acyclic: Vec<String>,
+    /// Number of halted ants which have been removed.
+    retired: usize,
+    /// Number of ants halted by the step limit.
+    step_limited: usize,
+    /// Whether the most recent halt was due to the step limit.
+    step_limited_last: bool,
+    cap: usize,
+    cap_policy: CapPolicy,
+    /// Number of ants which were evicted or not spawned, due to caps.
+    dropped: usize,
+    /// Number of live ants using each ruleset which has a spawn cap, by lowercase name.
+    live_counts: HashMap<String, usize>,
+    /// Set when the whole run is stopped early.
+    stopped: Option<Stop>,
+    started: Instant,
+    /// Slots remaining in the current tick, if a tick is in progress.
+    progress: Option<Range<usize>>,
+    collision: Collision,
+    update: Update,
+    halt: Halt,
+    region_policy: RegionPolicy,
+    protect_policy: ProtectPolicy,
+    /// Number of blocks ahead of each ant to cache before each tick, or `0` to not prefetch.
+    prefetch: u32,
+    /// Number of block writes outside the region which were not applied.
+    refused: usize,
+    /// Block writes to apply at the end of the tick, with the id of the writing ant.
+    ///
+    /// Only used with [`Update::Synchronous`].
+    pending_writes: HashMap<Coordinate, (usize, Block)>,
+    /// Cuboid fills to apply at the end of the tick, as the id of the filling ant, corners and
+    /// block. Where writes overlap, the ant with the lowest id wins, as with `pending_writes`.
+    ///
+    /// Only used with [`Update::Synchronous`].
+    pending_fills: Vec<(usize, Coordinate, Coordinate, Block)>,
+    /// Pheromone deposits to apply at the end of the tick, as channel, location and amount.
+    ///
+    /// Only used with [`Update::Synchronous`].
+    pending_deposits: Vec<(String, Coordinate, f64)>,
+    /// Position of each ant which has moved during the tick, before it moved, by id.
+    ///
+    /// Only used with [`Update::Synchronous`], where collisions are with the ants which were in
+    /// a cell at the start of the tick.
+    moved_from: HashMap<usize, Coordinate>,
+    /// Ants spawned during the current tick have this id or higher.
+    tick_first_id: usize,
+    cycles: CycleDetector,
+    /// Ids of ants to remove at the end of the tick.
+    merged: Vec<usize>,
+    /// Global assignments to apply at the end of the tick.
+    pending_assigns: Vec<(String, f64)>,
+    /// Signals to broadcast at the end of the tick.
+    pending_signals: Vec<(String, State)>,
+    breakpoints: Vec<Breakpoint>,
+    /// Id of the ant which hit a breakpoint, so that it is not hit again when the run resumes.
+    resume: Option<usize>,
+}
+
+/// Result of stepping the engine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    /// An ant was stepped, and the tick is still in progress.
+    Stepped,
+    TickComplete,
+    /// A breakpoint was hit before an ant applied its rule. The ant is stepped next.
+    Breakpoint,
+}
+
+/// Result of stepping an ant.
+#[derive(PartialEq)]
+enum Outcome {
+    Applied,
+    Halted,
+    Breakpoint,
+}
+
+/// State which is visible to rule conditions.
+struct Shared {
+    tick: u64,
+    /// Player position when the run started.
+    origin: Coordinate,
+    field: Field,
+    globals: HashMap<String, f64>,
+    /// Number of live ants in each cell.
+    occupancy: HashMap<Coordinate, u32>,
+    /// Set during a tick with [`Update::Synchronous`], so that occupancy stays as it was at the
+    /// start of the tick.
+    frozen: bool,
+    /// Minimum and maximum corners of the region, in worldspace.
+    region: Option<(Coordinate, Coordinate)>,
+}
+
+impl Shared {
+    fn in_region(&self, location: Coordinate) -> bool {
+        let Some((min, max)) = self.region else {
+            return true;
+        };
+        location.min(min) == min && location.max(max) == max
+    }
+
+    fn on_region_edge(&self, location: Coordinate) -> bool {
+        let Some((min, max)) = self.region else {
+            return false;
+        };
+        self.in_region(location)
+            && [Axis::X, Axis::Y, Axis::Z].into_iter().any(|axis| {
+                axis.get(location) == axis.get(min) || axis.get(location) == axis.get(max)
+            })
+    }
+
+    /// Returns the part of a cuboid which is inside the region, if any.
+    fn clamp_to_region(
+        &self,
+        min: Coordinate,
+        max: Coordinate,
+    ) -> Option<(Coordinate, Coordinate)> {
+        let Some((region_min, region_max)) = self.region else {
+            return Some((min, max));
+        };
+        let (min, max) = (min.max(region_min), max.min(region_max));
+        (min.min(max) == min).then_some((min, max))
+    }
+
+    fn occupants(&self, location: Coordinate) -> u32 {
+        self.occupancy.get(&location).copied().unwrap_or(0)
+    }
+
+    fn enter(&mut self, location: Coordinate) {
+        if self.frozen {
+            return;
+        }
+        *self.occupancy.entry(location).or_insert(0) += 1;
+    }
+
+    fn leave(&mut self, location: Coordinate) {
+        if self.frozen {
+            return;
+        }
+        if let Some(count) = self.occupancy.get_mut(&location) {
+            *count -= 1;
+            if *count == 0 {
+                self.occupancy.remove(&location);
+            }
+        }
+    }
+}
+
+impl Engine {
+    pub fn new(schema: Schema, mut world: World, origin: Coordinate) -> Self {
+        let mut ants = Ants::default();
+        for ant in &schema.ants {
+            let mut ant = ant.clone();
+            ant.position = origin + ant.offset;
+            ant.spawn_point = ant.position;
+            ants.insert(ant);
+        }
+
+        let mut signalled = Vec::new();
+        for ruleset in &schema.rulesets {
+            for rule in &ruleset.rules {
+                for action in &rule.actions {
+                    if let Action::Signal(name, _) = action {
+                        signalled.push(name.clone());
+                    }
+                }
+            }
+        }
+
+        let acyclic = (schema.rulesets.iter())
+            .filter(|ruleset| {
+                (signalled.iter()).any(|name| name.eq_ignore_ascii_case(&ruleset.name))
+                    || (ruleset.rules.iter())
+                        .flat_map(|rule| &rule.conditions)
+                        .any(|condition| !condition.quantity.is_positional())
+            })
+            .map(|ruleset| ruleset.name.clone())
+            .collect();
+
+        let field = Field::new(
+            schema.properties.decay.unwrap_or(DEFAULT_DECAY),
+            schema.properties.diffusion.unwrap_or(DEFAULT_DIFFUSION),
+        );
+        let globals = schema.globals.iter().cloned().collect();
+        let cap = schema.properties.cap.unwrap_or(DEAFULT_CAP);
+        let cap_policy = schema
+            .properties
+            .cap_policy
+            .unwrap_or(CapPolicy::EvictOldest);
+        let collision = schema.properties.collision.unwrap_or(Collision::Allow);
+        let update = schema.properties.update.unwrap_or(Update::Sequential);
+        let halt = schema.properties.halt.unwrap_or(Halt::Continue);
+        let region = (schema.properties.region).map(|region| region.resolve(origin));
+        let region_policy = schema
+            .properties
+            .region_policy
+            .unwrap_or(RegionPolicy::Halt);
+        let protect_policy = schema
+            .properties
+            .protect_policy
+            .unwrap_or(ProtectPolicy::Skip);
+        let prefetch = schema.properties.prefetch.unwrap_or(DEFAULT_PREFETCH);
+
+        if schema.properties.max_changes.is_some() {
+            world.enable_exact_changes();
+        }
+        world.set_protection(Protection::new(
+            schema.properties.protect.clone(),
+            (schema.properties.protect_regions.iter())
+                .map(|region| region.resolve(origin))
+                .collect(),
+        ));
+
+        let mut engine = Self {
+            schema: Rc::new(schema),
+            world,
+            ants,
+            shared: Shared {
+                tick: 0,
+                origin,
+                field,
+                globals,
+                occupancy: HashMap::new(),
+                frozen: false,
+                region,
+            },
+            signalled,
+            acyclic,
+            retired: 0,
+            step_limited: 0,
+            step_limited_last: false,
+            cap,
+            cap_policy,
+            dropped: 0,
+            live_counts: HashMap::new(),
+            stopped: None,
+            started: Instant::now(),
+            progress: None,
+            collision,
+            update,
+            halt,
+            region_policy,
+            protect_policy,
+            prefetch,
+            refused: 0,
+            pending_writes: HashMap::new(),
+            pending_fills: Vec::new(),
+            pending_deposits: Vec::new(),
+            moved_from: HashMap::new(),
+            tick_first_id: 0,
+            cycles: CycleDetector::default(),
+            merged: Vec::new(),
+            pending_assigns: Vec::new(),
+            pending_signals: Vec::new(),
+            breakpoints: Vec::new(),
+            resume: None,
+        };
+        engine.recount_live();
+        engine
+    }
+
+    /// Returns the reason if the run has ended.
+    pub fn stopped(&self) -> Option<Stop> {
+        if self.stopped.is_some() {
+            return self.stopped;
+        }
+        if self.ants.iter().all(|ant| ant.halted) {
+            if self.step_limited_last {
+                return Some(Stop::MaxSteps);
+            }
+            return Some(Stop::Halted);
+        }
+        None
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.shared.tick
+    }
+
+    pub fn changes(&self) -> u64 {
+        self.world.changes()
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Number of ants spawned by rules.
+    pub fn spawned(&self) -> usize {
+        self.ants.next_id() - self.schema.ants.len()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn refused(&self) -> usize {
+        self.refused
+    }
+
+    pub fn retired(&self) -> usize {
+        self.retired
+    }
+
+    pub fn step_limited(&self) -> usize {
+        self.step_limited
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        (index < self.breakpoints.len()).then(|| self.breakpoints.remove(index))
+    }
+
+    /// Send queued block writes, which is otherwise done at the end of each tick.
+    pub fn flush(&mut self) -> Result<(), mcrs::Error> {
+        self.world.flush()
+    }
+
+    /// Whether some ants have been stepped in the current tick, but not all.
+    pub fn in_tick(&self) -> bool {
+        self.progress.is_some()
+    }
+
+    /// Returns `None` if a tick is in progress.
+    pub fn snapshot(&self, schema_path: &str) -> Option<Snapshot> {
+        if self.progress.is_some() {
+            return None;
+        }
+
+        let mut globals: Vec<_> = (self.shared.globals.iter())
+            .map(|(name, value)| (name.clone(), *value))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Some(Snapshot {
+            schema_path: schema_path.to_string(),
+            schema_hash: snapshot::schema_hash(&self.schema),
+            tick: self.shared.tick,
+            origin: self.shared.origin,
+            next_id: self.ants.next_id(),
+            retired: self.retired,
+            dropped: self.dropped,
+            changes: self.world.changes(),
+            globals,
+            ants: self.ants.iter().cloned().collect(),
+            scents: (self.shared.field.values())
+                .map(|(channel, location, value)| (channel.to_string(), location, value))
+                .collect(),
+        })
+    }
+
+    /// Replace the state of a new engine with a snapshot.
+    ///
+    /// If the schema has changed since the snapshot, every ant must still have a ruleset with a
+    /// rule for its state.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), String> {
+        if snapshot.schema_hash != snapshot::schema_hash(&self.schema) {
+            for ant in &snapshot.ants {
+                let Some(ruleset) = find_ruleset(&self.schema, ant) else {
+                    return Err(format!(
+                        "schema changed incompatibly: ant {} uses undefined ruleset `{}`",
+                        ant.id, ant.ruleset
+                    ));
+                };
+                let handles_state = ruleset
+                    .rules
+                    .iter()
+                    .any(|rule| rule.from_state.is_empty() || rule.from_state.contains(&ant.state));
+                if !ant.halted && !handles_state {
+                    return Err(format!(
+                        "schema changed incompatibly: ruleset `{}` has no rule for state `{}` of ant {}",
+                        ant.ruleset, ant.state, ant.id
+                    ));
+                }
+            }
+            eprintln!("warning: schema changed since snapshot");
+        }
+
+        // Globals which are no longer defined are dropped, and new globals keep their initial value
+        for (name, value) in snapshot.globals {
+            if let Some(global) = self.shared.globals.get_mut(&name) {
+                *global = value;
+            }
+        }
+        for (channel, location, value) in snapshot.scents {
+            self.shared.field.deposit(&channel, location, value);
+        }
+
+        self.shared.tick = snapshot.tick;
+        self.shared.origin = snapshot.origin;
+        self.ants = Ants::restore(snapshot.ants, snapshot.next_id);
+        self.recount_live();
+        self.retired = snapshot.retired;
+        self.dropped = snapshot.dropped;
+        self.world.set_changes(snapshot.changes);
+        Ok(())
+    }
+
+    /// Complete the current tick, or run a whole tick if none is in progress.
+    ///
+    /// Returns early if a breakpoint is hit.
+    pub fn tick(&mut self) -> Result<Progress, mcrs::Error> {
+        loop {
+            match self.step()? {
+                Progress::Stepped => (),
+                progress => return Ok(progress),
+            }
+        }
+    }
+
+    /// Step the next live ant, starting a new tick if none is in progress.
+    pub fn step(&mut self) -> Result<Progress, mcrs::Error> {
+        if self.progress.is_none() && !self.begin_tick()? {
+            return Ok(Progress::TickComplete);
+        }
+
+        if let Some(slot) = self.next_live_slot() {
+            let outcome = self.step_ant(slot)?;
+            if outcome == Outcome::Breakpoint {
+                return Ok(Progress::Breakpoint);
+            }
+            self.progress.as_mut().unwrap().start += 1;
+            if outcome == Outcome::Halted && self.halt == Halt::Interrupt {
+                self.progress = Some(0..0);
+            }
+            self.check_limits();
+            if self.stopped.is_some() {
+                self.progress = Some(0..0);
+            }
+        }
+
+        if self.next_live_slot().is_some() {
+            return Ok(Progress::Stepped);
+        }
+        self.progress = None;
+        self.end_tick()?;
+        Ok(Progress::TickComplete)
+    }
+
+    /// Halt an ant by id. Returns `false` if the ant does not exist or is already halted.
+    pub fn halt(&mut self, id: usize) -> bool {
+        let Some(ant) = self.ants.get_mut(id).filter(|ant| !ant.halted) else {
+            return false;
+        };
+        ant.halted = true;
+        let position = ant.position;
+        let ruleset = ant.ruleset.clone();
+        self.shared.leave(position);
+        self.count_live(&ruleset, -1);
+        self.step_limited_last = false;
+        true
+    }
+
+    /// Returns `false` if the run has stopped.
+    fn begin_tick(&mut self) -> Result<bool, mcrs::Error> {
+        self.check_limits();
+        self.enforce_cap();
+        if self.stopped.is_some() {
+            return Ok(false);
+        }
+
+        if let Some(recorder) = self.world.recorder() {
+            recorder.tick(self.shared.tick).map_err(mcrs::Error::IO)?;
+        }
+
+        self.shared.frozen = false;
+        self.shared.occupancy.clear();
+        for ant in self.ants.iter().filter(|ant| !ant.halted) {
+            self.shared.enter(ant.position);
+        }
+        self.shared.frozen = self.update == Update::Synchronous;
+        self.moved_from.clear();
+        self.tick_first_id = self.ants.next_id();
+
+        // Load the blocks which ants are likely to read this tick, so that stepping does not wait
+        // for the server. Ants spawned last tick are still at their spawn points, so those are
+        // included.
+        if self.prefetch > 0 {
+            let prefetch = self.prefetch as i32;
+            let locations = (self.ants.iter().filter(|ant| !ant.halted)).flat_map(|ant| {
+                let offset = ant.facing.into_vec3();
+                (0..=prefetch)
+                    .map(move |distance| ant.position + offset.map(|value| value * distance))
+            });
+            self.world.prefetch(locations)?;
+        }
+
+        if self.world.shows_particles() {
+            for ant in self.ants.iter().filter(|ant| !ant.halted) {
+                let invisible = self.schema.properties.invisible;
+                (self.world).with_connection(|mc| show_ant_indicator(mc, ant, invisible))?;
+            }
+        }
+
+        // Ants spawned during this tick are not stepped until the next tick
+        self.progress = Some(0..self.ants.slot_count());
+        Ok(true)
+    }
+
+    /// Skip empty slots and halted ants in the current tick, without consuming the next slot.
+    fn next_live_slot(&mut self) -> Option<usize> {
+        let progress = self.progress.as_mut()?;
+        while progress.start < progress.end {
+            if self
+                .ants
+                .slot(progress.start)
+                .is_some_and(|ant| !ant.halted)
+            {
+                return Some(progress.start);
+            }
+            progress.start += 1;
+        }
+        None
+    }
+
+    fn check_limits(&mut self) {
+        let properties = &self.schema.properties;
+        let stop = if properties
+            .max_ticks
+            .is_some_and(|max_ticks| self.shared.tick >= max_ticks)
+        {
+            Stop::MaxTicks
+        } else if properties
+            .timeout
+            .is_some_and(|timeout| self.started.elapsed() >= timeout)
+        {
+            Stop::Timeout
+        } else if properties
+            .max_changes
+            .is_some_and(|max_changes| self.world.changes() >= max_changes)
+        {
+            Stop::MaxChanges
+        } else {
+            return;
+        };
+        self.stopped.get_or_insert(stop);
+    }
+
+    /// Remove ants over the cap, according to the cap policy.
+    fn enforce_cap(&mut self) {
+        let excess = self.ants.len().saturating_sub(self.cap);
+        if excess == 0 {
+            return;
+        }
+
+        match self.cap_policy {
+            // Checked when spawning instead
+            CapPolicy::Reject => return,
+
+            CapPolicy::EvictOldest => {
+                let evicted: Vec<usize> = (self.ants.iter())
+                    .filter(|ant| !ant.halted)
+                    .take(excess)
+                    .map(|ant| ant.id)
+                    .collect();
+                // Halted ants are retired at the end of the tick instead
+                self.dropped += evicted.len();
+                for id in evicted {
+                    self.remove_live(id);
+                }
+                return;
+            }
+
+            CapPolicy::EvictHalted => {
+                let mut evicted: Vec<usize> = (self.ants.iter())
+                    .filter(|ant| ant.halted)
+                    .take(excess)
+                    .map(|ant| ant.id)
+                    .collect();
+                let evict_live = excess - evicted.len();
+                evicted.extend(
+                    (self.ants.iter())
+                        .filter(|ant| !ant.halted)
+                        .take(evict_live)
+                        .map(|ant| ant.id),
+                );
+                for id in evicted {
+                    self.remove_live(id);
+                }
+            }
+
+            CapPolicy::Stop => {
+                println!("====[ CAP REACHED ]====");
+                self.stopped = Some(Stop::Cap);
+                return;
+            }
+        }
+
+        self.dropped += excess;
+    }
+
+    /// Returns `false` if the ant would exceed the total cap or its ruleset's cap.
+    fn can_spawn(&self, ruleset: &str) -> bool {
+        if self.cap_policy == CapPolicy::Reject && self.ants.len() >= self.cap {
+            return false;
+        }
+
+        let Some((_, cap)) = self
+            .schema
+            .properties
+            .spawn_caps
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(ruleset))
+        else {
+            return true;
+        };
+        let count = self.live_counts[&ruleset.to_ascii_lowercase()];
+        count < *cap
+    }
+
+    /// Count live ants from scratch, for rulesets with spawn caps.
+    fn recount_live(&mut self) {
+        self.live_counts = (self.schema.properties.spawn_caps.iter())
+            .map(|(name, _)| (name.to_ascii_lowercase(), 0))
+            .collect();
+        for ant in self.ants.iter().filter(|ant| !ant.halted) {
+            if let Some(count) = self.live_counts.get_mut(&ant.ruleset.to_ascii_lowercase()) {
+                *count += 1;
+            }
+        }
+    }
+
+    /// Update the number of live ants using a ruleset, if it has a spawn cap.
+    fn count_live(&mut self, ruleset: &str, change: isize) {
+        if self.live_counts.is_empty() {
+            return;
+        }
+        if let Some(count) = self.live_counts.get_mut(&ruleset.to_ascii_lowercase()) {
+            *count = count.saturating_add_signed(change);
+        }
+    }
+
+    /// Remove an ant, which may be live.
+    fn remove_live(&mut self, id: usize) {
+        if let Some(ant) = self.ants.remove(id)
+            && !ant.halted
+        {
+            self.count_live(&ant.ruleset, -1);
+        }
+    }
+
+    /// Apply changes which must not be visible until every ant has stepped.
+    ///
+    /// Assignments are applied in ant order, so the last assignment to a global wins.
+    fn end_tick(&mut self) -> Result<(), mcrs::Error> {
+        // Lowest ids are filled last, so they win where fills overlap
+        let mut fills = std::mem::take(&mut self.pending_fills);
+        fills.sort_by_key(|(id, ..)| std::cmp::Reverse(*id));
+        for &(id, min, max, block) in &fills {
+            let violations = self.world.violations();
+            self.world.fill(min, max, block)?;
+            if self.protect_policy == ProtectPolicy::Halt && self.world.violations() > violations {
+                self.halt(id);
+            }
+        }
+
+        for (location, (id, block)) in std::mem::take(&mut self.pending_writes) {
+            let filled = fills.iter().any(|&(fill_id, min, max, _)| {
+                fill_id < id && location.min(min) == min && location.max(max) == max
+            });
+            if filled {
+                continue;
+            }
+            let violations = self.world.violations();
+            self.world.set_block(location, block)?;
+            if self.protect_policy == ProtectPolicy::Halt && self.world.violations() > violations {
+                self.halt(id);
+            }
+        }
+
+        for id in self.merged.drain(..) {
+            self.ants.remove(id);
+        }
+
+        for (name, value) in self.pending_assigns.drain(..) {
+            self.shared.globals.insert(name, value);
+        }
+
+        for (ruleset, state) in std::mem::take(&mut self.pending_signals) {
+            let mut woken = 0;
+            for ant in self.ants.iter_mut() {
+                if ant.ruleset.eq_ignore_ascii_case(&ruleset) {
+                    woken += ant.halted as isize;
+                    ant.state = state.clone();
+                    ant.halted = false;
+                }
+            }
+            self.count_live(&ruleset, woken);
+        }
+
+        self.retire_halted();
+        self.ants.compact();
+        if self.schema.properties.cycles.is_some() {
+            let ants = &self.ants;
+            self.cycles.retain(|id| ants.contains(id));
+        }
+
+        for (channel, location, amount) in self.pending_deposits.drain(..) {
+            self.shared.field.deposit(&channel, location, amount);
+        }
+        self.shared.field.update();
+        self.shared.tick += 1;
+
+        self.world.flush()
+    }
+
+    /// Remove halted ants, unless they could be woken by a signal.
+    fn retire_halted(&mut self) {
+        let retired: Vec<usize> = (self.ants.iter())
+            .filter(|ant| {
+                ant.halted
+                    && !(self.signalled.iter())
+                        .any(|ruleset| ruleset.eq_ignore_ascii_case(&ant.ruleset))
+            })
+            .map(|ant| ant.id)
+            .collect();
+        self.retired += retired.len();
+        for id in retired {
+            self.ants.remove(id);
+        }
+    }
+
+    fn ant(&self, slot: usize) -> &Ant {
+        self.ants.slot(slot).expect("ant should exist")
+    }
+
+    fn ant_mut(&mut self, slot: usize) -> &mut Ant {
+        self.ants.slot_mut(slot).expect("ant should exist")
+    }
+
+    fn halt_ant(&mut self, slot: usize) {
+        let id = self.ant(slot).id;
+        self.halt(id);
+    }
+
+    fn step_ant(&mut self, slot: usize) -> Result<Outcome, mcrs::Error> {
+        let schema = Rc::clone(&self.schema);
+        let ant = self.ants.slot(slot).expect("ant should exist");
+        let block = self.world.get_block(ant.position)?;
+
+        print!(
+            "{:4} \t{:2} \t{:4} \t{:4} \t{} \t{} \t{:?} \t{} \t",
+            self.shared.tick,
+            ant.id,
+            ant.steps,
+            self.shared.tick - ant.born,
+            ant.position,
+            ant.state,
+            ant.facing,
+            block.get_name().unwrap_or("[unknown]"),
+        );
+
+        if (schema.properties.max_steps).is_some_and(|max_steps| ant.steps >= max_steps) {
+            println!("====[ STEP LIMIT ]====");
+            self.halt_ant(slot);
+            self.step_limited += 1;
+            self.step_limited_last = true;
+            return Ok(Outcome::Halted);
+        }
+
+        let rule = find_rule(&schema, &self.shared, ant, block);
+
+        if let Some((index, rule)) = rule
+            && self.resume.take() != Some(ant.id)
+            && let Some(hit) = self.find_breakpoint(ant, block, index, rule)
+        {
+            println!(
+                "====[ BREAKPOINT {}: {} ]====",
+                hit + 1,
+                self.breakpoints[hit]
+            );
+            self.resume = Some(ant.id);
+            self.print_context(slot, index, rule)?;
+            return Ok(Outcome::Breakpoint);
+        }
+
+        let cycle = match schema.properties.cycles {
+            None | Some(Cycles::Off) => None,
+            Some(_)
+                if (self.acyclic.iter())
+                    .any(|ruleset| ruleset.eq_ignore_ascii_case(&ant.ruleset)) =>
+            {
+                None
+            }
+            Some(_) => self.cycles.check(ant, self.world.changes()),
+        };
+        if let Some(length) = cycle {
+            if schema.properties.cycles == Some(Cycles::Halt) {
+                println!("====[ CYCLE DETECTED: {} steps ]====", length);
+                self.halt_ant(slot);
+                return Ok(Outcome::Halted);
+            }
+            print!("[cycle of {} steps] \t", length);
+        }
+
+        let Some((index, rule)) = rule else {
+            println!("====[ HALT ]====");
+            self.halt_ant(slot);
+            return Ok(Outcome::Halted);
+        };
+
+        print!("{} \t", rule.to_state);
+        if let Some(to_facing) = rule.to_facing {
+            print!("{:?}", to_facing);
+        } else {
+            print!("-");
+        }
+        print!(" \t");
+        if let Some(to_block) = rule.to_block {
+            print!("{}", to_block.get_name().unwrap_or("[unknown]"));
+        } else {
+            print!("-");
+        }
+        println!();
+
+        if let Some(recorder) = self.world.recorder() {
+            recorder.step(ant, index).map_err(mcrs::Error::IO)?;
+        }
+
+        let id = ant.id;
+        let previous_position = ant.position;
+        let violations = self.world.violations();
+        if let Some(to_block) = rule.to_block {
+            self.write_block(id, previous_position, to_block)?;
+        }
+        let ant = self.ant_mut(slot);
+        ant.state = rule.to_state.clone();
+        if let Some(to_facing) = rule.to_facing {
+            ant.facing = to_facing;
+        }
+        ant.steps += 1;
+        let facing = ant.facing;
+        self.move_ant(slot);
+
+        for action in &rule.actions {
+            match action {
+                Action::Spawn(spawn) => {
+                    if !self.can_spawn(&spawn.ruleset) {
+                        self.dropped += 1;
+                        continue;
+                    }
+                    let mut child = spawn.clone();
+                    child.position = previous_position;
+                    child.spawn_point = previous_position;
+                    child.born = self.shared.tick;
+                    self.shared.enter(child.position);
+                    self.count_live(&child.ruleset, 1);
+                    self.ants.insert(child);
+                }
+
+                Action::Stamp(name) => {
+                    let template = find_template(&schema, name);
+                    let blocks = template.blocks.iter().map(|(offset, block)| {
+                        (previous_position + facing.rotate(*offset), *block)
+                    });
+                    if self.update == Update::Synchronous {
+                        for (location, block) in blocks {
+                            self.write_block(id, location, block)?;
+                        }
+                    } else {
+                        let (inside, outside): (Vec<_>, Vec<_>) =
+                            blocks.partition(|(location, _)| self.shared.in_region(*location));
+                        self.refused += outside.len();
+                        self.world.set_blocks(inside)?;
+                    }
+                }
+
+                Action::Fill(block, fill) => {
+                    let (start, end) = fill.corners(facing);
+                    let (corner_a, corner_b) = (previous_position + start, previous_position + end);
+                    let (min, max) = (corner_a.min(corner_b), corner_a.max(corner_b));
+                    let clamped = self.shared.clamp_to_region(min, max);
+                    let inside = clamped.map_or(0, |(min, max)| volume(min, max));
+                    self.refused += volume(min, max) - inside;
+                    if let Some((min, max)) = clamped {
+                        match self.update {
+                            Update::Sequential => self.world.fill(min, max, *block)?,
+                            Update::Synchronous => self.pending_fills.push((id, min, max, *block)),
+                        }
+                    }
+                }
+
+                Action::Deposit(channel, amount) => match self.update {
+                    Update::Sequential => {
+                        (self.shared.field).deposit(channel, previous_position, *amount);
+                    }
+                    Update::Synchronous => {
+                        (self.pending_deposits).push((channel.clone(), previous_position, *amount));
+                    }
+                },
+
+                Action::Assign(name, value) => {
+                    self.pending_assigns.push((name.clone(), *value));
+                }
+
+                Action::Signal(ruleset, state) => {
+                    self.pending_signals.push((ruleset.clone(), state.clone()));
+                }
+            }
+        }
+
+        if self.protect_policy == ProtectPolicy::Halt
+            && self.world.violations() > violations
+            && !self.ant(slot).halted
+        {
+            println!("====[ HALT: PROTECTED BLOCK ]====");
+            self.halt_ant(slot);
+            return Ok(Outcome::Halted);
+        }
+
+        // Halted while moving, by the region or a collision
+        if self.ant(slot).halted {
+            return Ok(Outcome::Halted);
+        }
+
+        Ok(Outcome::Applied)
+    }
+
+    /// Returns the index of the first breakpoint matching the rule about to be applied.
+    fn find_breakpoint(&self, ant: &Ant, block: Block, index: usize, rule: &Rule) -> Option<usize> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+
+        let writes = |target: Block| {
+            rule.to_block == Some(target)
+                || rule.actions.iter().any(|action| match action {
+                    Action::Fill(block, _) => *block == target,
+                    Action::Stamp(name) => (find_template(&self.schema, name).blocks.iter())
+                        .any(|(_, block)| *block == target),
+                    _ => false,
+                })
+        };
+        let context = breakpoint::Context {
+            ant,
+            block,
+            rule_index: index,
+            writes: &writes,
+        };
+
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.matches(&context))
+    }
+
+    /// Print the rule an ant is about to apply, and the blocks around it.
+    fn print_context(&mut self, slot: usize, index: usize, rule: &Rule) -> Result<(), mcrs::Error> {
+        const NEIGHBOURS: &[Direction] = &[
+            Direction::East,
+            Direction::West,
+            Direction::South,
+            Direction::North,
+            Direction::Up,
+            Direction::Down,
+        ];
+
+        let ant = self.ant(slot).clone();
+        println!(
+            "ant {} ({}): rule {} -> {}, {}, {}",
+            ant.id,
+            ant.ruleset,
+            index + 1,
+            rule.to_state,
+            rule.to_block
+                .map_or("-", |block| block.get_name().unwrap_or("[unknown]")),
+            rule.to_facing
+                .map_or(String::from("-"), |facing| format!("{:?}", facing)),
+        );
+        for direction in NEIGHBOURS {
+            let location = ant.position + Coordinate::from(direction.into_vec3());
+            let block = self.world.get_block(location)?;
+            println!(
+                "    {:?} \t{} \t{}",
+                direction,
+                location,
+                block.get_name().unwrap_or("[unknown]"),
+            );
+        }
+        Ok(())
+    }
+
+    /// With [`Update::Synchronous`], the write is deferred to the end of the tick, and conflicting
+    /// writes to the same location are resolved in favour of the oldest ant (lowest id). Later
+    /// writes by the same ant replace its earlier writes.
+    fn write_block(
+        &mut self,
+        id: usize,
+        location: Coordinate,
+        block: Block,
+    ) -> Result<(), mcrs::Error> {
+        if !self.shared.in_region(location) {
+            self.refused += 1;
+            return Ok(());
+        }
+
+        match self.update {
+            Update::Sequential => self.world.set_block(location, block),
+            Update::Synchronous => {
+                let entry = self.pending_writes.entry(location).or_insert((id, block));
+                if id <= entry.0 {
+                    *entry = (id, block);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Move ant forward, according to the collision policy.
+    fn move_ant(&mut self, slot: usize) {
+        let ant = self.ant(slot);
+        let id = ant.id;
+        let from = ant.position;
+        let to = from + Coordinate::from(ant.facing.into_vec3());
+
+        if !self.shared.in_region(to) {
+            match self.region_policy {
+                RegionPolicy::Halt => self.halt_ant(slot),
+                RegionPolicy::Bounce => {
+                    let ant = self.ant_mut(slot);
+                    ant.facing = ant.facing.opposite();
+                }
+            }
+            return;
+        }
+
+        if self.shared.occupants(to) > 0 {
+            match self.collision {
+                Collision::Allow => (),
+
+                // Waiting for another ant to move is not a cycle
+                Collision::Block => {
+                    self.cycles.reset(id);
+                    return;
+                }
+
+                Collision::Halt => {
+                    let others: Vec<usize> = (self.ants.iter())
+                        .filter(|other| {
+                            !other.halted && self.tick_start_position(other) == Some(to)
+                        })
+                        .map(|other| other.id)
+                        .collect();
+                    for other in others {
+                        self.halt(other);
+                    }
+                    self.halt(id);
+                    return;
+                }
+
+                Collision::Merge => {
+                    self.merged.push(id);
+                    self.halt(id);
+                    return;
+                }
+            }
+        }
+
+        self.shared.leave(from);
+        self.shared.enter(to);
+        if self.update == Update::Synchronous {
+            self.moved_from.entry(id).or_insert(from);
+        }
+        self.ant_mut(slot).move_forward();
+    }
+
+    /// Position of an ant at the start of the tick, or `None` if it was spawned during the tick.
+    fn tick_start_position(&self, ant: &Ant) -> Option<Coordinate> {
+        match self.update {
+            Update::Sequential => Some(ant.position),
+            Update::Synchronous if ant.id >= self.tick_first_id => None,
+            Update::Synchronous => Some(
+                self.moved_from
+                    .get(&ant.id)
+                    .copied()
+                    .unwrap_or(ant.position),
+            ),
+        }
+    }
+}
+
+/// Returns the matching rule, and its index in the ruleset.
+fn find_rule<'a>(
+    schema: &'a Schema,
+    shared: &Shared,
+    ant: &Ant,
+    block: Block,
+) -> Option<(usize, &'a Rule)> {
+    let ruleset = find_ruleset(schema, ant)?;
+    ruleset.rules.iter().enumerate().find(|(_, rule)| {
+        (rule.from_state.is_empty() || rule.from_state.contains(&ant.state))
+            && (rule.from_block.is_empty() || rule.from_block.contains(&block))
+            && (rule.from_facing.is_empty() || rule.from_facing.contains(&ant.facing))
+            && rule
+                .conditions
+                .iter()
+                .all(|condition| evaluate_condition(shared, ant, condition))
+    })
+}
+
+fn evaluate_condition(shared: &Shared, ant: &Ant, condition: &Condition) -> bool {
+    let value = match &condition.quantity {
+        Quantity::Scent(channel) => shared.field.get(channel, ant.position),
+        Quantity::Global(name) => shared.globals[name],
+        Quantity::Steps => ant.steps as f64,
+        Quantity::Age => (shared.tick - ant.born) as f64,
+        Quantity::Tick => shared.tick as f64,
+        Quantity::Position(relative, axis) => {
+            axis.get(ant.position - reference_point(shared, ant, *relative)) as f64
+        }
+        Quantity::Distance(relative) => {
+            let offset = ant.position - reference_point(shared, ant, *relative);
+            let [x, y, z] = [offset.x, offset.y, offset.z].map(|value| value as f64);
+            (x * x + y * y + z * z).sqrt()
+        }
+        Quantity::Ahead => {
+            shared.occupants(ant.position + Coordinate::from(ant.facing.into_vec3())) as f64
+        }
+        // Exclude the ant itself
+        Quantity::Occupied => shared.occupants(ant.position).saturating_sub(1) as f64,
+        Quantity::Edge => shared.on_region_edge(ant.position) as u8 as f64,
+    };
+    condition.comparison.compare(value, condition.value)
+}
+
+/// Number of blocks in a cuboid, with corners in order.
+fn volume(min: Coordinate, max: Coordinate) -> usize {
+    let size = min.size_between(max);
+    (size.x as usize)
+        .saturating_mul(size.y as usize)
+        .saturating_mul(size.z as usize)
+}
+
+fn reference_point(shared: &Shared, ant: &Ant, relative: Relative) -> Coordinate {
+    match relative {
+        Relative::World => Coordinate::new(0, 0, 0),
+        Relative::Spawn => ant.spawn_point,
+        Relative::Origin => shared.origin,
+    }
+}
+
+fn find_ruleset<'a>(schema: &'a Schema, ant: &Ant) -> Option<&'a Ruleset> {
+    schema
+        .rulesets
+        .iter()
+        .find(|ruleset| ruleset.name.eq_ignore_ascii_case(&ant.ruleset))
+}
+
+fn find_template<'a>(schema: &'a Schema, name: &str) -> &'a Template {
+    schema
+        .templates
+        .iter()
+        .find(|template| template.name.eq_ignore_ascii_case(name))
+        .expect("template should exist")
+}
+
+/// Reason for a run ending.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    /// Every ant halted.
+    Halted,
+    /// Every ant halted, with the last ones halted by the step limit.
+    MaxSteps,
+    /// Ant cap exceeded, with [`CapPolicy::Stop`].
+    Cap,
+    MaxTicks,
+    Timeout,
+    MaxChanges,
+    /// Stopped by the user.
+    Quit,
+    /// Interrupted by Ctrl-C, at the end of a tick.
+    Interrupted,
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Halted => write!(f, "all ants halted"),
+            Self::Cap => write!(f, "ant cap exceeded"),
+            Self::MaxTicks => write!(f, "tick limit reached"),
+            Self::MaxSteps => write!(f, "step limit reached"),
+            Self::Timeout => write!(f, "timeout reached"),
+            Self::MaxChanges => write!(f, "block change limit reached"),
+            Self::Quit => write!(f, "quit by user"),
+            Self::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::backend::Memory;
+    use crate::parse::Parser;
+
+    /// Ant 1 halts on its first step, between two ants which walk east.
+    const SCHEMA: &str = "
+        ant use walker; facing east; end
+        ant use stuck; offset 0, 0, 2; end
+        ant use walker; facing east; offset 0, 0, 4; end
+
+        ruleset walker
+            0,, -> 0,,;
+        end
+
+        ruleset stuck
+            1,, -> 1,,;
+        end
+    ";
+
+    fn engine(properties: &str) -> Engine {
+        engine_from(&format!("{}\n{}", properties, SCHEMA))
+    }
+
+    fn engine_from(source: &str) -> Engine {
+        let schema = Parser::new(source)
+            .parse_schema()
+            .expect("schema should parse");
+        let world = World::new(Box::new(Memory::default()), 0, Duration::ZERO, 0);
+        Engine::new(schema, world, Coordinate::new(0, 0, 0))
+    }
+
+    /// Make ant 1 walk west instead of halting.
+    fn make_walker(engine: &mut Engine) {
+        let ant = engine.ants.get_mut(1).unwrap();
+        ant.ruleset = String::from("walker");
+        ant.state = String::from("0");
+        ant.facing = Direction::West;
+    }
+
+    fn position(engine: &Engine, id: usize) -> Option<Coordinate> {
+        (engine.ants.iter())
+            .find(|ant| ant.id == id)
+            .map(|ant| ant.position)
+    }
+
+    #[test]
+    fn halt_continue_steps_remaining_ants() {
+        let mut engine = engine("set halt continue");
+        engine.tick().unwrap();
+
+        assert_eq!(position(&engine, 0), Some(Coordinate::new(1, 0, 0)));
+        assert_eq!(position(&engine, 1), None, "halted ant should be retired");
+        assert_eq!(position(&engine, 2), Some(Coordinate::new(1, 0, 4)));
+    }
+
+    #[test]
+    fn halt_interrupt_skips_remaining_ants() {
+        let mut engine = engine("set halt interrupt");
+        engine.tick().unwrap();
+
+        assert_eq!(position(&engine, 0), Some(Coordinate::new(1, 0, 0)));
+        assert_eq!(position(&engine, 1), None, "halted ant should be retired");
+        assert_eq!(position(&engine, 2), Some(Coordinate::new(0, 0, 4)));
+
+        // Nothing halts in the next tick
+        engine.tick().unwrap();
+        assert_eq!(position(&engine, 0), Some(Coordinate::new(2, 0, 0)));
+        assert_eq!(position(&engine, 2), Some(Coordinate::new(1, 0, 4)));
+    }
+
+    #[test]
+    fn halt_is_default_continue() {
+        let mut engine = engine("");
+        engine.tick().unwrap();
+
+        assert_eq!(position(&engine, 2), Some(Coordinate::new(1, 0, 4)));
+    }
+
+    #[test]
+    fn region_halt_interrupts_tick() {
+        // Ant 1 walks out of the region before ant 2 steps
+        let mut engine = engine("set halt interrupt\nset region world 0 -1 -1 1 1 5");
+        make_walker(&mut engine);
+        engine.tick().unwrap();
+
+        assert_eq!(position(&engine, 0), Some(Coordinate::new(1, 0, 0)));
+        assert_eq!(position(&engine, 1), None, "halted ant should be retired");
+        assert_eq!(position(&engine, 2), Some(Coordinate::new(0, 0, 4)));
+    }
+
+    #[test]
+    fn collision_halt_interrupts_tick() {
+        // Ant 1 walks into ant 0, which halts both of them before ant 2 steps
+        let mut engine = engine("set halt interrupt\nset collision halt");
+        make_walker(&mut engine);
+        let ant = engine.ants.get_mut(1).unwrap();
+        ant.facing = Direction::North;
+        ant.position = Coordinate::new(1, 0, 1);
+        engine.tick().unwrap();
+
+        assert_eq!(position(&engine, 0), None);
+        assert_eq!(position(&engine, 1), None);
+        assert_eq!(position(&engine, 2), Some(Coordinate::new(0, 0, 4)));
+    }
+
+    #[test]
+    fn evict_oldest_keeps_halted_ants() {
+        let mut engine = engine("set cap 2\nset cap_policy evict_oldest");
+        make_walker(&mut engine);
+        engine.halt(0);
+        engine.tick().unwrap();
+
+        assert_eq!(
+            position(&engine, 1),
+            None,
+            "oldest live ant should be evicted"
+        );
+        assert_eq!(position(&engine, 2), Some(Coordinate::new(1, 0, 4)));
+        assert_eq!(engine.dropped(), 1);
+    }
+
+    #[test]
+    fn spawn_cap_counts_live_ants() {
+        let mut engine = engine_from(
+            "
+            set spawn_cap child 2
+            ant use spawner; end
+
+            ruleset spawner
+                0,, -> 0,, +spawn ant use child; end;
+            end
+
+            ruleset child
+                0,, -> 0,,;
+            end
+            ",
+        );
+        let children = |engine: &Engine| {
+            (engine.ants.iter())
+                .filter(|ant| ant.ruleset == "child")
+                .count()
+        };
+
+        for _ in 0..3 {
+            engine.tick().unwrap();
+        }
+        assert_eq!(children(&engine), 2);
+        assert_eq!(engine.dropped(), 1);
+
+        // Halted children do not count towards the cap
+        engine.halt(1);
+        engine.tick().unwrap();
+        assert_eq!(children(&engine), 2);
+        assert_eq!(engine.dropped(), 1);
+        assert_eq!(engine.live_counts["child"], 2);
+    }
+
+    fn state(engine: &Engine, id: usize) -> String {
+        (engine.ants.iter())
+            .find(|ant| ant.id == id)
+            .map(|ant| ant.state.clone())
+            .expect("ant should exist")
+    }
+
+    /// Ant 1 checks whether the cell ahead is occupied, after ant 0 moves into it in the same tick.
+    fn observer(update: &str) -> Engine {
+        engine_from(&format!(
+            "
+            set update {}
+            ant use leader; facing east; end
+            ant use observer; facing west; offset 2, 0, 0; end
+
+            ruleset leader
+                0,, -> 0,, +deposit trail 1;
+            end
+
+            ruleset observer
+                0,, if ahead > 0 -> blocked,,;
+                0,, -> clear,,;
+            end
+            ",
+            update
+        ))
+    }
+
+    #[test]
+    fn sequential_update_sees_earlier_ants() {
+        let mut engine = observer("sequential");
+        engine.tick().unwrap();
+
+        assert_eq!(state(&engine, 1), "blocked");
+    }
+
+    #[test]
+    fn synchronous_update_sees_start_of_tick() {
+        let mut engine = observer("synchronous");
+        engine.tick().unwrap();
+
+        assert_eq!(state(&engine, 1), "clear");
+        // Deposits are applied at the end of the tick
+        assert_eq!(
+            engine.shared.field.get("trail", Coordinate::new(0, 0, 0)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn synchronous_deposits_are_deferred() {
+        let mut engine = engine_from(
+            "
+            set update synchronous
+            ant use depositor; end
+            ant use sniffer; end
+
+            ruleset depositor
+                0,, -> 0,, +deposit trail 1;
+            end
+
+            ruleset sniffer
+                0,, if scent trail > 0 -> smelled,,;
+                0,, -> missed,,;
+            end
+            ",
+        );
+        engine.tick().unwrap();
+
+        assert_eq!(state(&engine, 1), "missed");
+    }
+
+    /// Ant 0 paces back and forth until tick 4, if the condition is given.
+    fn pacer(condition: &str) -> Engine {
+        engine_from(&format!(
+            "
+            set cycles halt
+            ant use pacer; end
+
+            ruleset pacer
+                {}
+                0,, -> 1,, west;
+                1,, -> 0,, east;
+            end
+            ",
+            condition
+        ))
+    }
+
+    #[test]
+    fn cycles_halt_pacing_ant() {
+        let mut engine = pacer("");
+        for _ in 0..4 {
+            engine.tick().unwrap();
+        }
+
+        assert_eq!(engine.stopped(), Some(Stop::Halted));
+    }
+
+    #[test]
+    fn cycles_ignore_waiting_ant() {
+        let mut engine = pacer("0,, if tick >= 4 -> done,,;");
+        for _ in 0..5 {
+            engine.tick().unwrap();
+        }
+
+        assert_eq!(state(&engine, 0), "done");
+    }
+
+    #[test]
+    fn cycles_ignore_blocked_ant() {
+        let mut engine = engine_from(
+            "
+            set cycles halt
+            set collision block
+            ant use walker; end
+            ant use walker; offset 1, 0, 0; end
+
+            ruleset walker
+                0,, -> 0,,;
+            end
+            ",
+        );
+        engine.tick().unwrap();
+        engine.tick().unwrap();
+
+        assert_eq!(position(&engine, 0), Some(Coordinate::new(1, 0, 0)));
+    }
+
+    #[test]
+    fn fill_forward_follows_vertical_facing() {
+        let mut engine = engine_from(
+            "
+            ant use filler; facing up; end
+
+            ruleset filler
+                0,, -> 1,, +fill stone forward 3;
+            end
+            ",
+        );
+        engine.tick().unwrap();
+
+        let world = &mut engine.world;
+        assert_eq!(
+            world.get_block(Coordinate::new(0, 2, 0)).unwrap(),
+            Block::STONE
+        );
+        assert_eq!(
+            world.get_block(Coordinate::new(2, 0, 0)).unwrap(),
+            Block::AIR
+        );
+    }
+
+    #[test]
+    fn synchronous_fills_conflict_by_lowest_id() {
+        let rulesets = "
+            ruleset filler
+                0,, -> 1,, +fill glass forward 3;
+            end
+
+            ruleset setter
+                0,, -> 1, stone,;
+            end
+        ";
+        let filler = "ant use filler; end";
+        let setter = "ant use setter; offset 1, 0, 0; end";
+        for (first, second, expected) in [
+            (filler, setter, Block::GLASS),
+            (setter, filler, Block::STONE),
+        ] {
+            let mut engine = engine_from(&format!(
+                "set update synchronous\n{}\n{}\n{}",
+                first, second, rulesets
+            ));
+            engine.tick().unwrap();
+
+            let world = &mut engine.world;
+            assert_eq!(
+                world.get_block(Coordinate::new(0, 0, 0)).unwrap(),
+                Block::GLASS
+            );
+            assert_eq!(world.get_block(Coordinate::new(1, 0, 0)).unwrap(), expected);
+            assert_eq!(
+                world.get_block(Coordinate::new(2, 0, 0)).unwrap(),
+                Block::GLASS
+            );
+        }
+    }
+
+    #[test]
+    fn step_limit_is_reported() {
+        let mut engine = engine("set max_steps 2");
+        make_walker(&mut engine);
+        for _ in 0..3 {
+            engine.tick().unwrap();
+        }
+
+        assert_eq!(engine.stopped(), Some(Stop::MaxSteps));
+        assert_eq!(engine.step_limited(), 3);
+    }
+}
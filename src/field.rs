@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use mcrs::Coordinate;
+
+use crate::rules::Direction;
+
+/// Invisible scalar values per cell, in named channels.
+///
+/// Stored by the engine only, never in the Minecraft world.
+pub struct Field {
+    channels: HashMap<String, HashMap<Coordinate, f64>>,
+    /// Proportion of each value which is lost every tick.
+    decay: f64,
+    /// Proportion of each value which is spread evenly to adjacent cells every tick.
+    diffusion: f64,
+}
+
+impl Field {
+    /// Values below this are removed entirely.
+    const MIN_VALUE: f64 = 0.001;
+
+    const NEIGHBORS: [Direction; 6] = [
+        Direction::East,
+        Direction::West,
+        Direction::South,
+        Direction::North,
+        Direction::Up,
+        Direction::Down,
+    ];
+
+    pub fn new(decay: f64, diffusion: f64) -> Self {
+        Self {
+            channels: HashMap::new(),
+            decay,
+            diffusion,
+        }
+    }
+
+    pub fn get(&self, channel: &str, location: Coordinate) -> f64 {
+        self.channels
+            .get(channel)
+            .and_then(|cells| cells.get(&location))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Every non-zero value, as channel, location and value.
+    pub fn values(&self) -> impl Iterator<Item = (&str, Coordinate, f64)> {
+        self.channels.iter().flat_map(|(channel, cells)| {
+            (cells.iter()).map(move |(location, value)| (channel.as_str(), *location, *value))
+        })
+    }
+
+    /// Negative amounts remove from the cell, without going below zero.
+    pub fn deposit(&mut self, channel: &str, location: Coordinate, amount: f64) {
+        if !self.channels.contains_key(channel) {
+            self.channels.insert(channel.to_string(), HashMap::new());
+        }
+        let cells = self.channels.get_mut(channel).unwrap();
+
+        let value = cells.entry(location).or_insert(0.0);
+        *value = (*value + amount).max(0.0);
+        if *value < Self::MIN_VALUE {
+            cells.remove(&location);
+        }
+    }
+
+    /// Apply diffusion then decay to every channel. Call once per tick.
+    pub fn update(&mut self) {
+        if self.decay <= 0.0 && self.diffusion <= 0.0 {
+            return;
+        }
+
+        for cells in self.channels.values_mut() {
+            let mut next = HashMap::with_capacity(cells.len());
+
+            for (location, value) in cells.iter() {
+                let spread = value * self.diffusion;
+                *next.entry(*location).or_insert(0.0) += value - spread;
+                for direction in Self::NEIGHBORS {
+                    let neighbor = *location + Coordinate::from(direction.into_vec3());
+                    *next.entry(neighbor).or_insert(0.0) += spread / Self::NEIGHBORS.len() as f64;
+                }
+            }
+
+            next.retain(|_, value| {
+                *value *= 1.0 - self.decay;
+                *value >= Self::MIN_VALUE
+            });
+            *cells = next;
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use mcrs::Coordinate;
+
+use crate::backend::Backend;
+use crate::rules::Ant;
+
+const COLORS: &[(f32, f32, f32)] = &[
+    (1.0, 0.0, 0.0),
+    (0.0, 1.0, 0.0),
+    (0.0, 0.0, 1.0),
+    (0.0, 1.0, 1.0),
+    (1.0, 0.0, 1.0),
+    (1.0, 1.0, 0.0),
+    (1.0, 0.5, 0.5),
+    (0.5, 1.0, 0.5),
+    (0.5, 0.5, 1.0),
+    (0.5, 1.0, 1.0),
+    (1.0, 0.5, 1.0),
+    (1.0, 1.0, 0.5),
+    (0.5, 0.0, 0.0),
+    (0.0, 0.5, 0.0),
+    (0.0, 0.0, 0.5),
+    (0.0, 0.5, 0.5),
+    (0.5, 0.0, 0.5),
+    (0.5, 0.5, 0.0),
+];
+
+pub fn show_ant_indicator(
+    mc: &mut dyn Backend,
+    ant: &Ant,
+    invisible: bool,
+) -> Result<(), mcrs::Error> {
+    let color = COLORS[ant.id % COLORS.len()];
+
+    if invisible {
+        // create_block_particle(mc, ant.position, color, 0, 0.1, 0.5, 1.5, false)?;
+    } else {
+        create_block_particle(mc, ant.position, color, 4, 0.4, 0.5, 0.6, false)?;
+        create_block_particle(mc, ant.position, color, 3, 0.8, 0.5, 1.0, true)?;
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_block_particle(
+    mc: &mut dyn Backend,
+    position: Coordinate,
+    // RGB
+    color: (f32, f32, f32),
+    // Number of particles in cube, per direction
+    count: i32,
+    // Size of block (half)
+    radius: f32,
+    // Offset fix in blocks
+    correction: f32,
+    // Larger particle size means longer duration
+    size: f32,
+    // Show particles as a sphere, not a cube
+    round: bool,
+) -> Result<(), mcrs::Error> {
+    // Particle positions get rounded to nearest half-block by Minecraft
+
+    for x in -count..=count {
+        for y in -count..=count {
+            for z in -count..=count {
+                let offset = [
+                    (x as f32 / count.max(1) as f32) * radius,
+                    (y as f32 / count.max(1) as f32) * radius,
+                    (z as f32 / count.max(1) as f32) * radius,
+                ];
+
+                if round && (offset[0].powi(2) + offset[1].powi(2) + offset[2].powi(2)) > radius {
+                    continue;
+                }
+
+                mc.do_command(format_args!(
+                    // Indirect execution to stop errors being spammed to player's chat
+                    "execute at @a run particle dust {r} {g} {b} {size} {x} {y} {z}",
+                    r = color.0,
+                    g = color.1,
+                    b = color.2,
+                    size = size,
+                    x = position.x as f32 + offset[0] + correction,
+                    y = position.y as f32 + offset[1] + correction,
+                    z = position.z as f32 + offset[2] + correction,
+                ))?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+
+use mcrs::{Block, Coordinate};
+
+use crate::backend::Backend;
+use crate::text::{parse_block, parse_coordinate, parse_lines};
+
+/// Append-only record of the block at each location before it was written.
+///
+/// Each line is `x y z id:modifier`. Lines are written before the block is changed, so the
+/// journal is complete even if the process is killed.
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Opens the file for appending, so consecutive runs can be undone together.
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    pub fn record(&mut self, location: Coordinate, previous: Block) -> io::Result<()> {
+        // Single write per line, so a crash can only truncate the last line
+        let line = format!(
+            "{} {} {} {}:{}\n",
+            location.x, location.y, location.z, previous.id, previous.modifier
+        );
+        self.file.write_all(line.as_bytes())
+    }
+}
+
+/// Restore every block recorded in a journal, most recent first.
+///
+/// Returns the number of blocks restored.
+pub fn undo(mc: &mut dyn Backend, path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)?;
+    let entries = parse_lines(text.lines().enumerate(), "journal", parse_line)?;
+
+    for (location, block) in entries.iter().rev() {
+        mc.set_block(*location, *block)?;
+    }
+    Ok(entries.len())
+}
+
+fn parse_line(line: &str) -> Option<(Coordinate, Block)> {
+    let mut words = line.split_whitespace();
+    let location = parse_coordinate(&mut words)?;
+    let block = parse_block(words.next())?;
+    if words.next().is_some() {
+        return None;
+    }
+    Some((location, block))
+}
@@ -1,226 +1,257 @@
+mod ants;
+mod args;
+mod backend;
+mod batch;
+mod breakpoint;
+mod control;
+mod cycles;
+mod engine;
+mod field;
+mod indicator;
+mod journal;
+mod overlay;
 mod parse;
+mod protect;
+mod replay;
 mod rules;
+mod server;
+mod snapshot;
+mod text;
 mod world;
 
 use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use mcrs::{Block, Coordinate};
-
+use self::args::{Args, Command};
+use self::backend::{Backend, Memory};
+use self::breakpoint::Breakpoint;
+use self::control::Control;
+use self::engine::{Engine, Stop};
+use self::journal::Journal;
 use self::parse::Parser;
-use self::rules::{Ant, Rule, Ruleset, Schema};
+use self::replay::Recorder;
+use self::rules::{Ant, Properties};
+use self::server::Server;
+use self::snapshot::Snapshot;
 
 const DEFAULT_DELAY: Duration = Duration::from_millis(100);
-const DEAFULT_CAP: usize = 50;
 const DEFAULT_CACHE_SIZE: u32 = 4;
 const DEFAULT_CACHE_TIME: Duration = Duration::from_secs(8);
-
-const COLORS: &[(f32, f32, f32)] = &[
-    (1.0, 0.0, 0.0),
-    (0.0, 1.0, 0.0),
-    (0.0, 0.0, 1.0),
-    (0.0, 1.0, 1.0),
-    (1.0, 0.0, 1.0),
-    (1.0, 1.0, 0.0),
-    (1.0, 0.5, 0.5),
-    (0.5, 1.0, 0.5),
-    (0.5, 0.5, 1.0),
-    (0.5, 1.0, 1.0),
-    (1.0, 0.5, 1.0),
-    (1.0, 1.0, 0.5),
-    (0.5, 0.0, 0.0),
-    (0.0, 0.5, 0.0),
-    (0.0, 0.0, 0.5),
-    (0.0, 0.5, 0.5),
-    (0.5, 0.0, 0.5),
-    (0.5, 0.5, 0.0),
-];
+const DEFAULT_CACHE_LIMIT: usize = 256;
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 4711;
+const DEFAULT_RECONNECT: u32 = 5;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = std::env::args();
-    args.next();
+    let args = Args::parse()?;
+
+    if args.command == Command::Undo {
+        let mut mc = connect(&args, &server(&args, &Properties::default()))?;
+        let count = journal::undo(mc.as_mut(), &args.filepath)?;
+        println!("Restored {} blocks", count);
+        return Ok(());
+    }
 
-    let filepath = args.next().ok_or("missing filepath")?;
+    if args.command == Command::Play {
+        let mut mc = connect(&args, &server(&args, &Properties::default()))?;
+        let ticks = replay::play(mc.as_mut(), &args.filepath, args.speed.unwrap_or(1.0))?;
+        println!("Played {} ticks", ticks);
+        return Ok(());
+    }
 
-    let text = fs::read_to_string(filepath)?;
+    let snapshot = match args.command {
+        Command::Resume => Some(Snapshot::load(&args.filepath)?),
+        _ => None,
+    };
+    let schema_path = match &snapshot {
+        Some(snapshot) => snapshot.schema_path.clone(),
+        None => args.filepath.clone(),
+    };
+    // Resumed runs keep saving to the same snapshot, by default
+    let snapshot_path = args
+        .snapshot
+        .clone()
+        .or_else(|| snapshot.is_some().then(|| args.filepath.clone()));
+
+    let text = fs::read_to_string(&schema_path)?;
 
     let mut parser = Parser::new(&text);
-    let schema = parser.parse_schema()?;
-
-    let mut mc = mcrs::Connection::new()?;
+    let mut schema = parser.parse_schema()?;
+
+    let properties = &mut schema.properties;
+    properties.max_ticks = args.max_ticks.or(properties.max_ticks);
+    properties.max_steps = args.max_steps.or(properties.max_steps);
+    properties.timeout = args.timeout.or(properties.timeout);
+    properties.max_changes = args.max_changes.or(properties.max_changes);
+    for name in &args.protect {
+        let block = Parser::parse_block(name).ok_or_else(|| format!("unknown block `{}`", name))?;
+        properties.protect.push(block);
+    }
+    for source in &args.protect_regions {
+        let values: Vec<&str> = source.split(',').map(str::trim).collect();
+        let region = Parser::parse_region("--protect-region", &values)?;
+        properties.protect_regions.push(region);
+    }
 
-    let player = mc.get_player_position()?;
+    let breakpoints = (args.breakpoints.iter())
+        .map(|source| Breakpoint::parse(source))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let mut max_id = 0;
+    let server = server(&args, &schema.properties);
+    let mut mc = connect(&args, &server)?;
 
-    let mut ants = schema.ants.clone();
-    for ant in &mut ants {
-        ant.position = player + ant.offset;
-        ant.id = max_id;
-        max_id += 1;
-    }
+    let origin = match &snapshot {
+        Some(snapshot) => snapshot.origin,
+        None => mc.get_player_position()?,
+    };
 
     let delay = schema.properties.delay.unwrap_or(DEFAULT_DELAY);
-    let cap = schema.properties.cap.unwrap_or(DEAFULT_CAP);
 
     let mut world = world::World::new(
         mc,
         schema.properties.cache_size.unwrap_or(DEFAULT_CACHE_SIZE),
         schema.properties.cache_time.unwrap_or(DEFAULT_CACHE_TIME),
+        schema.properties.cache_limit.unwrap_or(DEFAULT_CACHE_LIMIT),
     );
 
-    while !ants.iter().all(|ant| ant.halted) {
-        while ants.len() > cap {
-            ants.remove(0);
-        }
+    if !args.offline {
+        world.set_server(server);
+    }
+    if schema.properties.batch.unwrap_or(true) {
+        world.enable_batching();
+    }
 
-        std::thread::sleep(delay);
+    if args.dry_run {
+        world.enable_dry_run();
+    } else if let Some(path) = &args.journal {
+        world.set_journal(Journal::open(path)?);
+    }
+    if let Some(path) = &args.record {
+        world.set_recorder(Recorder::create(path, delay)?);
+    }
 
-        for ant in ants.iter().filter(|ant| !ant.halted) {
-            show_ant_indicator(world.get_mc(), ant, schema.properties.invisible)?;
-        }
+    let mut engine = Engine::new(schema, world, origin);
+    if let Some(snapshot) = snapshot {
+        engine.restore(snapshot)?;
+    }
+    for breakpoint in breakpoints {
+        engine.add_breakpoint(breakpoint);
+    }
 
-        let len = ants.len();
-        for i in 0..len {
-            let ant = &mut ants[i];
-            if ant.halted {
-                continue;
-            }
+    let mut control = args
+        .interactive
+        .then(|| Control::new(delay, schema_path.clone(), snapshot_path.clone()));
 
-            let block = world.get_block(ant.position)?;
-
-            print!(
-                "{:2} \t{} \t{} \t{:?} \t{} \t",
-                i,
-                ant.position,
-                ant.state,
-                ant.facing,
-                block.get_name().unwrap_or("[unknown]"),
-            );
-
-            let Some(rule) = find_rule(&schema, ant, block) else {
-                println!("====[ HALT ]====");
-                ant.halted = true;
-                break;
-            };
-
-            print!("{} \t", rule.to_state);
-            if let Some(to_facing) = rule.to_facing {
-                print!("{:?}", to_facing);
-            } else {
-                print!("-");
-            }
-            print!(" \t");
-            if let Some(to_block) = rule.to_block {
-                print!("{}", to_block.get_name().unwrap_or("[unknown]"));
-            } else {
-                print!("-");
-            }
-            println!();
+    let mut last_saved = engine.ticks();
 
-            let previous_position = ant.position;
-            if let Some(to_block) = rule.to_block {
-                world.set_block(ant.position, to_block)?;
-            }
-            ant.state = rule.to_state.clone();
-            if let Some(to_facing) = rule.to_facing {
-                ant.facing = to_facing;
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = Arc::clone(&interrupted);
+        ctrlc::set_handler(move || {
+            // Second Ctrl-C exits immediately, in case the server is not responding
+            if interrupted.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
             }
-            ant.move_forward();
-
-            if let Some(spawn) = &rule.spawn {
-                let mut child = spawn.clone();
-                child.position = previous_position;
-                child.id = max_id;
-                max_id += 1;
-                ants.push(child);
+            eprintln!("Stopping at the end of the tick, press Ctrl-C again to exit now");
+        })?;
+    }
+
+    let stop = loop {
+        if let Some(stop) = engine.stopped() {
+            break stop;
+        }
+
+        if interrupted.load(Ordering::SeqCst) {
+            // Finish the current tick, so that the snapshot can be saved
+            while engine.in_tick() && engine.stopped().is_none() {
+                engine.tick()?;
             }
+            break engine.stopped().unwrap_or(Stop::Interrupted);
         }
 
-        std::thread::sleep(schema.properties.delay.unwrap_or(DEFAULT_DELAY));
-    }
+        if let Some(control) = &mut control {
+            if let Some(stop) = control.update(&mut engine)? {
+                break stop;
+            }
+        } else {
+            std::thread::sleep(delay);
+            engine.tick()?;
+            std::thread::sleep(delay);
+        }
 
-    Ok(())
-}
+        if let (Some(path), Some(autosave)) = (&snapshot_path, args.autosave)
+            && engine.ticks() >= last_saved + autosave
+            && let Some(snapshot) = engine.snapshot(&schema_path)
+        {
+            snapshot.save(path)?;
+            last_saved = engine.ticks();
+        }
+    };
 
-fn show_ant_indicator(
-    mc: &mut mcrs::Connection,
-    ant: &Ant,
-    invisible: bool,
-) -> Result<(), mcrs::Error> {
-    let color = COLORS[ant.id % COLORS.len()];
+    // Run may have stopped during a tick
+    engine.flush()?;
 
-    if invisible {
-        // create_block_particle(mc, ant.position, color, 0, 0.1, 0.5, 1.5, false)?;
-    } else {
-        create_block_particle(mc, ant.position, color, 4, 0.4, 0.5, 0.6, false)?;
-        create_block_particle(mc, ant.position, color, 3, 0.8, 0.5, 1.0, true)?;
+    if let Some(path) = &snapshot_path {
+        match engine.snapshot(&schema_path) {
+            Some(snapshot) => {
+                snapshot.save(path)?;
+                println!("Saved snapshot to {}", path);
+            }
+            None => eprintln!("Snapshot not saved, as the run stopped during a tick"),
+        }
     }
 
-    Ok(())
-}
+    println!("====[ STOPPED: {} ]====", stop);
+    println!("Ticks: {}", engine.ticks());
+    println!("Blocks changed: {}", engine.changes());
+    println!("Retired {} halted ants", engine.retired());
+    if engine.step_limited() > 0 {
+        println!("Halted {} ants at the step limit", engine.step_limited());
+    }
+    if engine.dropped() > 0 {
+        println!("Dropped {} ants due to cap", engine.dropped());
+    }
+    if engine.world().violations() > 0 {
+        println!(
+            "Skipped {} writes to protected blocks",
+            engine.world().violations()
+        );
+    }
+    if engine.refused() > 0 {
+        println!("Refused {} block writes outside region", engine.refused());
+    }
 
-fn create_block_particle(
-    mc: &mut mcrs::Connection,
-    position: Coordinate,
-    // RGB
-    color: (f32, f32, f32),
-    // Number of particles in cube, per direction
-    count: i32,
-    // Size of block (half)
-    radius: f32,
-    // Offset fix in blocks
-    correction: f32,
-    // Larger particle size means longer duration
-    size: f32,
-    // Show particles as a sphere, not a cube
-    round: bool,
-) -> Result<(), mcrs::Error> {
-    // Particle positions get rounded to nearest half-block by Minecraft
-
-    for x in -count..=count {
-        for y in -count..=count {
-            for z in -count..=count {
-                let offset = [
-                    (x as f32 / count.max(1) as f32) * radius,
-                    (y as f32 / count.max(1) as f32) * radius,
-                    (z as f32 / count.max(1) as f32) * radius,
-                ];
-
-                if round && (offset[0].powi(2) + offset[1].powi(2) + offset[2].powi(2)) > radius {
-                    continue;
-                }
-
-                mc.do_command(format_args!(
-                    // Indirect execution to stop errors being spammed to player's chat
-                    "execute at @a run particle dust {r} {g} {b} {size} {x} {y} {z}",
-                    r = color.0,
-                    g = color.1,
-                    b = color.2,
-                    size = size,
-                    x = position.x as f32 + offset[0] + correction,
-                    y = position.y as f32 + offset[1] + correction,
-                    z = position.z as f32 + offset[2] + correction,
-                ))?;
-            }
+    if let Some(overlay) = engine.world().overlay() {
+        println!("Spawned {} ants", engine.spawned());
+        overlay.print_summary();
+        if let Some(path) = &args.diff {
+            overlay.export(path)?;
+            println!("Exported diff to {}", path);
         }
     }
 
     Ok(())
 }
 
-fn find_rule<'a>(schema: &'a Schema, ant: &Ant, block: Block) -> Option<&'a Rule> {
-    let ruleset = find_ruleset(schema, ant)?;
-    ruleset.rules.iter().find(|rule| {
-        (rule.from_state.is_empty() || rule.from_state.contains(&ant.state))
-            && (rule.from_block.is_empty() || rule.from_block.contains(&block))
-            && (rule.from_facing.is_empty() || rule.from_facing.contains(&ant.facing))
-    })
+fn connect(args: &Args, server: &Server) -> std::io::Result<Box<dyn Backend>> {
+    if args.offline {
+        return Ok(Box::new(Memory::default()));
+    }
+    Ok(Box::new(server.connect()?))
 }
 
-fn find_ruleset<'a>(schema: &'a Schema, ant: &Ant) -> Option<&'a Ruleset> {
-    schema
-        .rulesets
-        .iter()
-        .find(|ruleset| ruleset.name.eq_ignore_ascii_case(&ant.ruleset))
+/// Options override the corresponding schema properties.
+fn server(args: &Args, properties: &Properties) -> Server {
+    Server::new(
+        (args.host.clone())
+            .or_else(|| properties.host.clone())
+            .unwrap_or_else(|| DEFAULT_HOST.to_string()),
+        args.port.or(properties.port).unwrap_or(DEFAULT_PORT),
+        args.connect_timeout.or(properties.connect_timeout),
+        (args.reconnect)
+            .or(properties.reconnect)
+            .unwrap_or(DEFAULT_RECONNECT),
+    )
 }
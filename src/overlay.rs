@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+
+use mcrs::{Block, Coordinate};
+
+/// Blocks written during a dry run, which are kept here instead of being sent to the server.
+#[derive(Default)]
+pub struct Overlay {
+    /// Original and current block of each written location.
+    blocks: HashMap<Coordinate, (Block, Block)>,
+}
+
+impl Overlay {
+    pub fn get(&self, location: Coordinate) -> Option<Block> {
+        self.blocks.get(&location).map(|(_, current)| *current)
+    }
+
+    /// `original` is only used on the first write to a location.
+    pub fn set(&mut self, location: Coordinate, original: Block, block: Block) {
+        self.blocks
+            .entry(location)
+            .and_modify(|(_, current)| *current = block)
+            .or_insert((original, block));
+    }
+
+    /// Locations which differ from the original world, as location, original and new block,
+    /// ordered by location.
+    pub fn changes(&self) -> Vec<(Coordinate, Block, Block)> {
+        let mut changes: Vec<_> = (self.blocks.iter())
+            .filter(|(_, (original, current))| original != current)
+            .map(|(location, (original, current))| (*location, *original, *current))
+            .collect();
+        changes.sort_by_key(|(location, _, _)| (location.x, location.y, location.z));
+        changes
+    }
+
+    pub fn print_summary(&self) {
+        let changes = self.changes();
+        println!("====[ DRY RUN: {} blocks would change ]====", changes.len());
+
+        let Some(first) = changes.first() else {
+            return;
+        };
+        let (min, max) = changes
+            .iter()
+            .fold((first.0, first.0), |(min, max), (location, _, _)| {
+                (min.min(*location), max.max(*location))
+            });
+        println!("Bounding box: {} to {}", min, max);
+
+        let mut counts: HashMap<Block, usize> = HashMap::new();
+        for (_, _, block) in &changes {
+            *counts.entry(*block).or_default() += 1;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        for (block, count) in counts {
+            println!("{:8} \t{}", count, block_name(block));
+        }
+    }
+
+    /// Write every change as a line of `x y z original new`.
+    pub fn export(&self, path: &str) -> Result<(), String> {
+        let mut text = String::new();
+        for (location, original, block) in self.changes() {
+            writeln!(
+                text,
+                "{} {} {} {} {}",
+                location.x,
+                location.y,
+                location.z,
+                block_name(original),
+                block_name(block),
+            )
+            .unwrap();
+        }
+        fs::write(path, text).map_err(|error| format!("failed to write diff `{}`: {}", path, error))
+    }
+}
+
+/// Name as used in schemas, or `id:modifier` for unnamed blocks.
+fn block_name(block: Block) -> String {
+    match block.get_name() {
+        Some(name) => name.to_lowercase(),
+        None => format!("{}:{}", block.id, block.modifier),
+    }
+}
@@ -9,7 +9,11 @@ use mcrs::{Block, Coordinate};
 use self::tokens::{TokenKind, Tokens};
 use crate::Ant;
 use crate::parse::tokens::Token;
-use crate::rules::{Direction, Properties, Rule, Ruleset, Schema};
+use crate::rules::{
+    Action, Axis, CapPolicy, Collision, Comparison, Condition, Cycles, Direction, Fill, Halt,
+    Properties, ProtectPolicy, Quantity, Region, RegionPolicy, Relative, Rule, Ruleset, Schema,
+    Template, Update,
+};
 
 pub struct Parser<'a> {
     tokens: Peekable<Tokens<'a>>,
@@ -27,11 +31,13 @@ impl<'a> Parser<'a> {
     pub fn parse_schema(&mut self) -> Result<Schema, String> {
         let mut ants = Vec::<Ant>::new();
         let mut rulesets = Vec::<Ruleset>::new();
+        let mut templates = Vec::<Template>::new();
+        let mut globals = Vec::<(String, f64)>::new();
         let mut properties = Properties::default();
 
         while !self.is_end() {
-            if let Some((property, value)) = self.try_property_set()? {
-                Self::update_property(&mut properties, property, value)?;
+            if let Some((property, values)) = self.try_property_set()? {
+                Self::update_property(&mut properties, property, &values)?;
                 continue;
             }
 
@@ -43,6 +49,14 @@ impl<'a> Parser<'a> {
                 continue;
             };
 
+            if let Some((name, value)) = self.try_global()? {
+                if globals.iter().any(|(other, _)| *other == name) {
+                    return Err(format!("duplicate global `{}`", name));
+                }
+                globals.push((name, value));
+                continue;
+            }
+
             if let Some(ant) = self.try_ant()? {
                 ants.push(ant);
                 continue;
@@ -60,6 +74,18 @@ impl<'a> Parser<'a> {
                 continue;
             }
 
+            if let Some(template) = self.try_template()? {
+                if templates
+                    .iter()
+                    .any(|other| other.name.eq_ignore_ascii_case(&template.name))
+                {
+                    return Err(format!("duplicate template `{}`", template.name));
+                }
+
+                templates.push(template);
+                continue;
+            }
+
             return Err(format!(
                 "expected {} or {}, found {}",
                 TokenKind::KwRuleset,
@@ -69,12 +95,28 @@ impl<'a> Parser<'a> {
         }
 
         for ant in &ants {
-            Self::ensure_ruleset_exists(&rulesets, ant)?;
+            Self::ensure_ruleset_exists(&rulesets, &ant.ruleset)?;
+        }
+        for (name, _) in &properties.spawn_caps {
+            Self::ensure_ruleset_exists(&rulesets, name)?;
         }
         for ruleset in &rulesets {
             for rule in &ruleset.rules {
-                if let Some(spawn) = &rule.spawn {
-                    Self::ensure_ruleset_exists(&rulesets, spawn)?;
+                for condition in &rule.conditions {
+                    if let Quantity::Global(name) = &condition.quantity {
+                        Self::ensure_global_exists(&globals, name)?;
+                    }
+                }
+                for action in &rule.actions {
+                    match action {
+                        Action::Spawn(spawn) => {
+                            Self::ensure_ruleset_exists(&rulesets, &spawn.ruleset)?
+                        }
+                        Action::Stamp(name) => Self::ensure_template_exists(&templates, name)?,
+                        Action::Assign(name, _) => Self::ensure_global_exists(&globals, name)?,
+                        Action::Signal(name, _) => Self::ensure_ruleset_exists(&rulesets, name)?,
+                        Action::Fill(..) | Action::Deposit(..) => (),
+                    }
                 }
             }
         }
@@ -82,16 +124,35 @@ impl<'a> Parser<'a> {
         Ok(Schema {
             ants,
             rulesets,
+            templates,
+            globals,
             properties,
         })
     }
 
-    fn ensure_ruleset_exists(rulesets: &[Ruleset], ant: &Ant) -> Result<(), String> {
+    fn ensure_ruleset_exists(rulesets: &[Ruleset], name: &str) -> Result<(), String> {
         if !rulesets
             .iter()
-            .any(|ruleset| ruleset.name.eq_ignore_ascii_case(&ant.ruleset))
+            .any(|ruleset| ruleset.name.eq_ignore_ascii_case(name))
         {
-            return Err(format!("unknown ruleset `{}`", ant.ruleset));
+            return Err(format!("unknown ruleset `{}`", name));
+        }
+        Ok(())
+    }
+
+    fn ensure_template_exists(templates: &[Template], name: &str) -> Result<(), String> {
+        if !templates
+            .iter()
+            .any(|template| template.name.eq_ignore_ascii_case(name))
+        {
+            return Err(format!("unknown template `{}`", name));
+        }
+        Ok(())
+    }
+
+    fn ensure_global_exists(globals: &[(String, f64)], name: &str) -> Result<(), String> {
+        if !globals.iter().any(|(other, _)| other == name) {
+            return Err(format!("undefined global `{}`", name));
         }
         Ok(())
     }
@@ -99,8 +160,61 @@ impl<'a> Parser<'a> {
     fn update_property(
         properties: &mut Properties,
         property: &str,
-        value: &str,
+        values: &[&str],
     ) -> Result<(), String> {
+        if property.eq_ignore_ascii_case("spawn_cap") {
+            let [ruleset, value] = values else {
+                return Err(format!(
+                    "expected ruleset and number for property `{}`",
+                    property
+                ));
+            };
+            let number: usize = Self::parse_numeric(value)?;
+            if properties
+                .spawn_caps
+                .iter()
+                .any(|(other, _)| other.eq_ignore_ascii_case(ruleset))
+            {
+                return Err(format!(
+                    "duplicate property `{}` for ruleset `{}`",
+                    property, ruleset
+                ));
+            }
+            properties.spawn_caps.push((ruleset.to_string(), number));
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("region") {
+            let region = Self::parse_region(property, values)?;
+            if properties.region.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.region = Some(region);
+            return Ok(());
+        }
+
+        // May be set multiple times
+        if property.eq_ignore_ascii_case("protect") {
+            for value in values {
+                let block =
+                    Self::parse_block(value).ok_or_else(|| format!("unknown block `{}`", value))?;
+                properties.protect.push(block);
+            }
+            return Ok(());
+        }
+
+        // May be set multiple times
+        if property.eq_ignore_ascii_case("protect_region") {
+            let region = Self::parse_region(property, values)?;
+            properties.protect_regions.push(region);
+            return Ok(());
+        }
+
+        let [value] = values else {
+            return Err(format!("expected single value for property `{}`", property));
+        };
+        let value = *value;
+
         if property.eq_ignore_ascii_case("delay") {
             let millis: u64 = Self::parse_numeric(value)?;
             if properties.delay.is_some() {
@@ -148,18 +262,256 @@ impl<'a> Parser<'a> {
             return Ok(());
         }
 
+        if property.eq_ignore_ascii_case("host") {
+            if properties.host.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.host = Some(value.to_string());
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("port") {
+            let number: u16 = Self::parse_numeric(value)?;
+            if properties.port.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.port = Some(number);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("connect_timeout") {
+            let secs = u64::from(Self::parse_nonzero(value)?);
+            if properties.connect_timeout.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.connect_timeout = Some(Duration::from_secs(secs));
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("reconnect") {
+            let number: u32 = Self::parse_numeric(value)?;
+            if properties.reconnect.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.reconnect = Some(number);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("cachelimit") {
+            let number: usize = Self::parse_numeric(value)?;
+            if properties.cache_limit.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.cache_limit = Some(number);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("prefetch") {
+            let number: u32 = Self::parse_numeric(value)?;
+            if properties.prefetch.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.prefetch = Some(number);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("decay") {
+            let proportion = Self::parse_proportion(value)?;
+            if properties.decay.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.decay = Some(proportion);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("diffusion") {
+            let proportion = Self::parse_proportion(value)?;
+            if properties.diffusion.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.diffusion = Some(proportion);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("collision") {
+            let collision = Self::parse_choice(
+                property,
+                value,
+                &[
+                    ("allow", Collision::Allow),
+                    ("block", Collision::Block),
+                    ("halt", Collision::Halt),
+                    ("merge", Collision::Merge),
+                ],
+            )?;
+            if properties.collision.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.collision = Some(collision);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("update") {
+            let update = Self::parse_choice(
+                property,
+                value,
+                &[
+                    ("sequential", Update::Sequential),
+                    ("synchronous", Update::Synchronous),
+                ],
+            )?;
+            if properties.update.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.update = Some(update);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("halt") {
+            let halt = Self::parse_choice(
+                property,
+                value,
+                &[("continue", Halt::Continue), ("interrupt", Halt::Interrupt)],
+            )?;
+            if properties.halt.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.halt = Some(halt);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("cap_policy") {
+            let cap_policy = Self::parse_choice(
+                property,
+                value,
+                &[
+                    ("reject", CapPolicy::Reject),
+                    ("evict_halted", CapPolicy::EvictHalted),
+                    ("evict_oldest", CapPolicy::EvictOldest),
+                    ("stop", CapPolicy::Stop),
+                ],
+            )?;
+            if properties.cap_policy.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.cap_policy = Some(cap_policy);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("max_ticks") {
+            let number: u64 = Self::parse_numeric(value)?;
+            if properties.max_ticks.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.max_ticks = Some(number);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("max_steps") {
+            let number: u64 = Self::parse_numeric(value)?;
+            if properties.max_steps.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.max_steps = Some(number);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("timeout") {
+            let secs: u64 = Self::parse_numeric(value)?;
+            if properties.timeout.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.timeout = Some(Duration::from_secs(secs));
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("max_changes") {
+            let number: u64 = Self::parse_numeric(value)?;
+            if properties.max_changes.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.max_changes = Some(number);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("region_policy") {
+            let region_policy = Self::parse_choice(
+                property,
+                value,
+                &[
+                    ("halt", RegionPolicy::Halt),
+                    ("bounce", RegionPolicy::Bounce),
+                ],
+            )?;
+            if properties.region_policy.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.region_policy = Some(region_policy);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("protect_policy") {
+            let protect_policy = Self::parse_choice(
+                property,
+                value,
+                &[("skip", ProtectPolicy::Skip), ("halt", ProtectPolicy::Halt)],
+            )?;
+            if properties.protect_policy.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.protect_policy = Some(protect_policy);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("batch") {
+            let batch = Self::parse_choice(property, value, &[("true", true), ("false", false)])?;
+            if properties.batch.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.batch = Some(batch);
+            return Ok(());
+        }
+
+        if property.eq_ignore_ascii_case("cycles") {
+            let cycles = Self::parse_choice(
+                property,
+                value,
+                &[
+                    ("off", Cycles::Off),
+                    ("warn", Cycles::Warn),
+                    ("halt", Cycles::Halt),
+                ],
+            )?;
+            if properties.cycles.is_some() {
+                return Err(format!("duplicate property `{}`", property));
+            }
+            properties.cycles = Some(cycles);
+            return Ok(());
+        }
+
         Err(format!("unknown property `{}`", property))
     }
 
-    fn try_property_set(&mut self) -> Result<Option<(&'a str, &'a str)>, String> {
+    fn try_property_set(&mut self) -> Result<Option<(&'a str, Vec<&'a str>)>, String> {
         if self.try_token_kind(TokenKind::KwSet).is_none() {
             return Ok(None);
         }
 
         let property = self.expect_ident_no_expand()?;
-        let value = self.expect_ident()?;
+        let mut values = vec![self.expect_ident()?];
+        loop {
+            // Commas are optional separators, as in `set region 0,0,0 9,9,9`
+            if self.try_token_kind(TokenKind::Comma).is_some() {
+                values.push(self.expect_ident()?);
+                continue;
+            }
+            let Some(value) = self.try_ident() else {
+                break;
+            };
+            values.push(value?);
+        }
 
-        Ok(Some((property, value)))
+        Ok(Some((property, values)))
     }
 
     fn try_symbol_define(&mut self) -> Result<Option<(&'a str, &'a str)>, String> {
@@ -178,6 +530,17 @@ impl<'a> Parser<'a> {
         Ok(Some((symbol, definition)))
     }
 
+    fn try_global(&mut self) -> Result<Option<(String, f64)>, String> {
+        if self.try_token_kind(TokenKind::KwGlobal).is_none() {
+            return Ok(None);
+        }
+
+        let name = self.expect_global_name()?;
+        let value = Self::parse_numeric(self.expect_ident()?)?;
+
+        Ok(Some((name, value)))
+    }
+
     fn try_ant(&mut self) -> Result<Option<Ant>, String> {
         if self.try_token_kind(TokenKind::KwAnt).is_none() {
             return Ok(None);
@@ -261,10 +624,13 @@ impl<'a> Parser<'a> {
             ruleset,
             offset: offset.unwrap_or(Coordinate::new(0, 0, 0)),
             position: Coordinate::new(0, 0, 0),
+            spawn_point: Coordinate::new(0, 0, 0),
             facing: facing.unwrap_or(DEFAULT_DIRECTION),
             state: state.unwrap_or(DEFAULT_STATE).to_string(),
             halted: false,
             id: 0,
+            steps: 0,
+            born: 0,
         }))
     }
 
@@ -290,6 +656,87 @@ impl<'a> Parser<'a> {
         Ok(Some(Ruleset { name, rules }))
     }
 
+    fn try_template(&mut self) -> Result<Option<Template>, String> {
+        if self.try_token_kind(TokenKind::KwTemplate).is_none() {
+            return Ok(None);
+        }
+
+        let name = self.expect_token_kind(TokenKind::Ident)?.string.to_string();
+
+        let mut keys = HashMap::<char, Block>::new();
+        let mut layers = Vec::<Vec<&str>>::new();
+
+        while !self
+            .tokens
+            .peek()
+            .is_none_or(|token| token.kind == TokenKind::KwEnd)
+        {
+            let next = self.tokens.next().unwrap();
+            match next.kind {
+                TokenKind::KwKey => {
+                    let key = self.expect_ident_no_expand()?;
+                    let ident = self.expect_ident()?;
+                    self.expect_token_kind(TokenKind::Semicolon)?;
+
+                    let mut chars = key.chars();
+                    let (Some(key), None) = (chars.next(), chars.next()) else {
+                        return Err(format!("template key `{}` must be a single character", key));
+                    };
+                    if key == TEMPLATE_EMPTY {
+                        return Err(format!("cannot redefine template key `{}`", key));
+                    }
+                    if keys.contains_key(&key) {
+                        return Err(format!("duplicate template key `{}`", key));
+                    }
+                    let block = Self::parse_block(ident)
+                        .ok_or_else(|| format!("unknown block `{}`", ident))?;
+                    keys.insert(key, block);
+                }
+
+                TokenKind::KwLayer => {
+                    let mut rows = Vec::new();
+                    loop {
+                        rows.push(self.expect_ident()?);
+                        if self.try_token_kind(TokenKind::Comma).is_none() {
+                            break;
+                        }
+                    }
+                    self.expect_token_kind(TokenKind::Semicolon)?;
+                    layers.push(rows);
+                }
+
+                _ => {
+                    return Err(format!(
+                        "expected {}, {}, or {}, found {}",
+                        TokenKind::KwKey,
+                        TokenKind::KwLayer,
+                        TokenKind::KwEnd,
+                        next.kind,
+                    ));
+                }
+            }
+        }
+        self.expect_token_kind(TokenKind::KwEnd)?;
+
+        // Layers are stacked upwards, rows go forwards, and columns go rightwards
+        let mut blocks = Vec::new();
+        for (up, rows) in layers.iter().enumerate() {
+            for (forward, row) in rows.iter().enumerate() {
+                for (right, key) in row.chars().enumerate() {
+                    if key == TEMPLATE_EMPTY {
+                        continue;
+                    }
+                    let Some(block) = keys.get(&key) else {
+                        return Err(format!("undefined template key `{}`", key));
+                    };
+                    blocks.push(([right as i32, up as i32, forward as i32], *block));
+                }
+            }
+        }
+
+        Ok(Some(Template { name, blocks }))
+    }
+
     fn expect_rule(&mut self) -> Result<Rule, String> {
         assert!(!self.is_end());
 
@@ -318,7 +765,18 @@ impl<'a> Parser<'a> {
             };
             from_facing.push(facing);
         }
-        self.expect_list_end(TokenKind::Arrow)?;
+        let mut conditions = Vec::new();
+        if self.try_token_kind(TokenKind::KwIf).is_some() {
+            loop {
+                conditions.push(self.expect_condition()?);
+                if self.try_token_kind(TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+            self.expect_token_kind(TokenKind::Arrow)?;
+        } else {
+            self.expect_list_end(TokenKind::Arrow)?;
+        }
 
         let to_state = self.expect_ident()?.to_string();
         self.expect_list_end(TokenKind::Comma)?;
@@ -343,20 +801,10 @@ impl<'a> Parser<'a> {
             }
         };
 
-        let spawn = if self
-            .tokens
-            .peek()
-            .is_some_and(|token| token.kind == TokenKind::Plus)
-        {
-            _ = self.tokens.next().unwrap();
-            self.expect_token_kind(TokenKind::KwSpawn)?;
-            let Some(ant) = self.try_ant()? else {
-                return Err(format!("expected `{}`", TokenKind::KwAnt));
-            };
-            Some(ant)
-        } else {
-            None
-        };
+        let mut actions = Vec::new();
+        while self.try_token_kind(TokenKind::Plus).is_some() {
+            actions.push(self.expect_action()?);
+        }
 
         self.expect_list_end(TokenKind::Semicolon)?;
 
@@ -364,13 +812,166 @@ impl<'a> Parser<'a> {
             from_state,
             from_block,
             from_facing,
+            conditions,
             to_state,
             to_block,
             to_facing,
-            spawn,
+            actions,
+        })
+    }
+
+    fn expect_action(&mut self) -> Result<Action, String> {
+        let Some(next) = self.tokens.next() else {
+            return Err(String::from("expected action, found eof"));
+        };
+        match next.kind {
+            TokenKind::KwSpawn => {
+                let Some(ant) = self.try_ant()? else {
+                    return Err(format!("expected {}", TokenKind::KwAnt));
+                };
+                Ok(Action::Spawn(ant))
+            }
+
+            TokenKind::KwStamp => {
+                let name = self.expect_ident()?.to_string();
+                Ok(Action::Stamp(name))
+            }
+
+            TokenKind::KwFill => {
+                let ident = self.expect_ident()?;
+                let block =
+                    Self::parse_block(ident).ok_or_else(|| format!("unknown block `{}`", ident))?;
+                let shape = self.expect_ident()?;
+                let fill = if shape.eq_ignore_ascii_case("forward") {
+                    Fill::Forward(Self::parse_length(self.expect_ident()?)?)
+                } else if shape.eq_ignore_ascii_case("box") {
+                    Fill::Box(Self::parse_dimensions(self.expect_ident()?)?)
+                } else {
+                    return Err(format!("unknown fill shape `{}`", shape));
+                };
+                if fill.volume() > MAX_VOLUME {
+                    return Err(format!("fill is larger than {} blocks", MAX_VOLUME));
+                }
+                Ok(Action::Fill(block, fill))
+            }
+
+            TokenKind::KwDeposit => {
+                let channel = self.expect_ident()?.to_string();
+                let amount: f64 = Self::parse_numeric(self.expect_ident()?)?;
+                Ok(Action::Deposit(channel, amount))
+            }
+
+            TokenKind::KwSet => {
+                let name = self.expect_global_name()?;
+                let value = Self::parse_numeric(self.expect_ident()?)?;
+                Ok(Action::Assign(name, value))
+            }
+
+            TokenKind::KwSignal => {
+                let ruleset = self.expect_ident()?.to_string();
+                let state = self.expect_ident()?.to_string();
+                Ok(Action::Signal(ruleset, state))
+            }
+
+            _ => Err(format!(
+                "expected {}, {}, {}, {}, {}, or {}, found {}",
+                TokenKind::KwSpawn,
+                TokenKind::KwStamp,
+                TokenKind::KwFill,
+                TokenKind::KwDeposit,
+                TokenKind::KwSet,
+                TokenKind::KwSignal,
+                next.kind,
+            )),
+        }
+    }
+
+    fn expect_condition(&mut self) -> Result<Condition, String> {
+        // `not <quantity>` is shorthand for `<quantity> = 0`
+        if self
+            .tokens
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::Ident && token.string == "not")
+        {
+            _ = self.tokens.next().unwrap();
+            return Ok(Condition {
+                quantity: self.expect_quantity()?,
+                comparison: Comparison::Equal,
+                value: 0.0,
+            });
+        }
+
+        let quantity = self.expect_quantity()?;
+
+        // `<quantity>` is shorthand for `<quantity> > 0`
+        if self
+            .tokens
+            .peek()
+            .is_none_or(|token| token.kind != TokenKind::Ident)
+        {
+            return Ok(Condition {
+                quantity,
+                comparison: Comparison::Greater,
+                value: 0.0,
+            });
+        }
+
+        let operator = self.expect_ident()?;
+        let comparison = Self::parse_comparison(operator)
+            .ok_or_else(|| format!("unknown comparison `{}`", operator))?;
+
+        let value = Self::parse_numeric(self.expect_ident()?)?;
+
+        Ok(Condition {
+            quantity,
+            comparison,
+            value,
         })
     }
 
+    fn expect_quantity(&mut self) -> Result<Quantity, String> {
+        if self.try_token_kind(TokenKind::KwGlobal).is_some() {
+            return Ok(Quantity::Global(self.expect_global_name()?));
+        }
+
+        const QUANTITIES: &[(&str, Quantity)] = &[
+            ("steps", Quantity::Steps),
+            ("age", Quantity::Age),
+            ("tick", Quantity::Tick),
+            ("x", Quantity::Position(Relative::World, Axis::X)),
+            ("y", Quantity::Position(Relative::World, Axis::Y)),
+            ("z", Quantity::Position(Relative::World, Axis::Z)),
+            ("dx", Quantity::Position(Relative::Spawn, Axis::X)),
+            ("dy", Quantity::Position(Relative::Spawn, Axis::Y)),
+            ("dz", Quantity::Position(Relative::Spawn, Axis::Z)),
+            ("dist", Quantity::Distance(Relative::Spawn)),
+            ("ox", Quantity::Position(Relative::Origin, Axis::X)),
+            ("oy", Quantity::Position(Relative::Origin, Axis::Y)),
+            ("oz", Quantity::Position(Relative::Origin, Axis::Z)),
+            ("odist", Quantity::Distance(Relative::Origin)),
+            ("ahead", Quantity::Ahead),
+            ("occupied", Quantity::Occupied),
+            ("edge", Quantity::Edge),
+        ];
+
+        let name = self.expect_ident()?;
+        if name.eq_ignore_ascii_case("scent") {
+            return Ok(Quantity::Scent(self.expect_ident()?.to_string()));
+        }
+        for (other, quantity) in QUANTITIES {
+            if other.eq_ignore_ascii_case(name) {
+                return Ok(quantity.clone());
+            }
+        }
+
+        Err(format!("unknown quantity `{}`", name))
+    }
+
+    /// Global names are case-insensitive.
+    fn expect_global_name(&mut self) -> Result<String, String> {
+        Ok(self.expect_ident_no_expand()?.to_ascii_lowercase())
+    }
+
     fn is_end(&mut self) -> bool {
         self.tokens.peek().is_none()
     }
@@ -450,7 +1051,104 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_block(string: &str) -> Option<Block> {
+    fn parse_nonzero(string: &str) -> Result<u32, String> {
+        let number: u32 = Self::parse_numeric(string)?;
+        if number == 0 {
+            return Err(String::from("expected non-zero number"));
+        }
+        Ok(number)
+    }
+
+    /// Parse a length in blocks, which must fit inside the world.
+    fn parse_length(string: &str) -> Result<i32, String> {
+        let number: i32 = Self::parse_numeric(string)?;
+        if !(1..=MAX_LENGTH).contains(&number) {
+            return Err(format!("expected length between 1 and {}", MAX_LENGTH));
+        }
+        Ok(number)
+    }
+
+    /// Parse dimensions in the form `WxHxD`.
+    fn parse_dimensions(string: &str) -> Result<[i32; 3], String> {
+        let mut parts = string.split(['x', 'X']);
+        let (Some(width), Some(height), Some(depth), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("expected dimensions `WxHxD`, found `{}`", string));
+        };
+        Ok([
+            Self::parse_length(width)?,
+            Self::parse_length(height)?,
+            Self::parse_length(depth)?,
+        ])
+    }
+
+    fn parse_proportion(string: &str) -> Result<f64, String> {
+        let number: f64 = Self::parse_numeric(string)?;
+        if !(0.0..=1.0).contains(&number) {
+            return Err(String::from("expected number between 0 and 1"));
+        }
+        Ok(number)
+    }
+
+    /// Two corners, optionally preceded by `world` or `origin` (the default).
+    pub fn parse_region(property: &str, values: &[&str]) -> Result<Region, String> {
+        let (relative, values) = match values {
+            [first, rest @ ..] if first.eq_ignore_ascii_case("world") => (Relative::World, rest),
+            [first, rest @ ..] if first.eq_ignore_ascii_case("origin") => (Relative::Origin, rest),
+            _ => (Relative::Origin, values),
+        };
+        let [x1, y1, z1, x2, y2, z2] = values else {
+            return Err(format!(
+                "expected two corners `x,y,z` for property `{}`",
+                property
+            ));
+        };
+        let corner = |x: &str, y: &str, z: &str| -> Result<Coordinate, String> {
+            Ok(Coordinate::new(
+                Self::parse_numeric(x)?,
+                Self::parse_numeric(y)?,
+                Self::parse_numeric(z)?,
+            ))
+        };
+        Ok(Region {
+            corners: [corner(x1, y1, z1)?, corner(x2, y2, z2)?],
+            relative,
+        })
+    }
+
+    fn parse_choice<T: Copy>(
+        property: &str,
+        value: &str,
+        choices: &[(&str, T)],
+    ) -> Result<T, String> {
+        for (name, choice) in choices {
+            if name.eq_ignore_ascii_case(value) {
+                return Ok(*choice);
+            }
+        }
+        Err(format!("invalid value for property `{}`", property))
+    }
+
+    fn parse_comparison(string: &str) -> Option<Comparison> {
+        const COMPARISONS: &[(&str, Comparison)] = &[
+            ("<", Comparison::Less),
+            ("<=", Comparison::LessEqual),
+            (">", Comparison::Greater),
+            (">=", Comparison::GreaterEqual),
+            ("=", Comparison::Equal),
+            ("!=", Comparison::NotEqual),
+        ];
+
+        for (name, comparison) in COMPARISONS {
+            if *name == string {
+                return Some(*comparison);
+            }
+        }
+        None
+    }
+
+    pub fn parse_block(string: &str) -> Option<Block> {
         for (name, block) in mcrs::BLOCKS {
             if name.eq_ignore_ascii_case(string) {
                 return Some(block);
@@ -459,7 +1157,7 @@ impl<'a> Parser<'a> {
         None
     }
 
-    fn parse_direction(string: &str) -> Option<Direction> {
+    pub fn parse_direction(string: &str) -> Option<Direction> {
         const DIRECTIONS: &[(&str, Direction)] = &[
             ("east", Direction::East),
             ("west", Direction::West),
@@ -478,6 +1176,16 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Template key for a cell which is left unchanged.
+const TEMPLATE_EMPTY: char = '_';
+
+/// Largest fill length or dimension, which is the width of a Minecraft world, so that offsets can
+/// be added to positions without overflowing.
+const MAX_LENGTH: i32 = 60_000_000;
+
+/// Largest number of blocks in a fill, so that the blocks it replaces can be held in memory.
+const MAX_VOLUME: u64 = 1_000_000;
+
 fn remove_first_char(string: &str) -> &str {
     let mut chars = string.chars();
     chars.next();
@@ -23,6 +23,15 @@ pub enum TokenKind {
     KwFacing,
     KwState,
     KwSpawn,
+    KwTemplate,
+    KwKey,
+    KwLayer,
+    KwStamp,
+    KwFill,
+    KwDeposit,
+    KwIf,
+    KwGlobal,
+    KwSignal,
     Ident,
 }
 
@@ -53,6 +62,15 @@ impl TokenKind {
             "facing" => Self::KwFacing,
             "state" => Self::KwState,
             "spawn" => Self::KwSpawn,
+            "template" => Self::KwTemplate,
+            "key" => Self::KwKey,
+            "layer" => Self::KwLayer,
+            "stamp" => Self::KwStamp,
+            "fill" => Self::KwFill,
+            "deposit" => Self::KwDeposit,
+            "if" => Self::KwIf,
+            "global" => Self::KwGlobal,
+            "signal" => Self::KwSignal,
             _ => Self::Ident,
         }
     }
@@ -76,6 +94,15 @@ impl fmt::Display for TokenKind {
             Self::KwFacing => write!(f, "`facing`"),
             Self::KwState => write!(f, "`state`"),
             Self::KwSpawn => write!(f, "`spawn`"),
+            Self::KwTemplate => write!(f, "`template`"),
+            Self::KwKey => write!(f, "`key`"),
+            Self::KwLayer => write!(f, "`layer`"),
+            Self::KwStamp => write!(f, "`stamp`"),
+            Self::KwFill => write!(f, "`fill`"),
+            Self::KwDeposit => write!(f, "`deposit`"),
+            Self::KwIf => write!(f, "`if`"),
+            Self::KwGlobal => write!(f, "`global`"),
+            Self::KwSignal => write!(f, "`signal`"),
             Self::Ident => write!(f, "<identifier>"),
         }
     }
@@ -97,7 +124,10 @@ impl CharKind {
             _ if ch.is_ascii_whitespace() => Self::Whitespace,
             ',' | ';' | '(' | ')' | '[' | ']' | '{' | '}' => Self::Atomic,
             '-' => Self::Any,
-            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '$' => Self::Combining { is_symbol: false },
+            // `.` is included for decimal numbers
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '$' | '.' => {
+                Self::Combining { is_symbol: false }
+            }
             _ => Self::Combining { is_symbol: true },
         }
     }
@@ -123,6 +153,12 @@ impl<'a> Tokens<'a> {
         Some(ch)
     }
 
+    fn peek_second_char(&self) -> Option<char> {
+        let mut chars = self.text[self.cursor..].chars();
+        _ = chars.next()?;
+        chars.next()
+    }
+
     fn next_char(&mut self) -> Option<char> {
         let mut chars = self.text[self.cursor..].chars();
         let ch = chars.next()?;
@@ -184,6 +220,15 @@ impl<'a> Tokens<'a> {
             match CharKind::from(ch) {
                 CharKind::Whitespace => break,
                 CharKind::Atomic => break,
+                // A `-` after a symbol which starts a number is a sign, as in `>=-5`
+                CharKind::Any
+                    if token_is_symbol == Some(true)
+                        && self.peek_second_char().is_some_and(|ch| {
+                            CharKind::from(ch) == CharKind::Combining { is_symbol: false }
+                        }) =>
+                {
+                    break;
+                }
                 CharKind::Any => (),
                 CharKind::Combining { is_symbol } => {
                     if let Some(token_is_symbol) = token_is_symbol {
@@ -232,3 +277,24 @@ impl<'a> Iterator for Tokens<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(text: &str) -> Vec<&str> {
+        Tokens::new(text).map(|token| token.string).collect()
+    }
+
+    #[test]
+    fn sign_is_split_from_comparison() {
+        assert_eq!(strings("x >=-5"), ["x", ">=", "-5"]);
+        assert_eq!(strings("y<-2"), ["y", "<", "-2"]);
+        assert_eq!(strings("z != -1"), ["z", "!=", "-1"]);
+    }
+
+    #[test]
+    fn arrow_is_one_token() {
+        assert_eq!(strings("0,, -> 1"), ["0", ",", ",", "->", "1"]);
+    }
+}
@@ -0,0 +1,38 @@
+use mcrs::{Block, Coordinate};
+
+/// Blocks which must never be overwritten, by type or by location.
+#[derive(Default)]
+pub struct Protection {
+    blocks: Vec<Block>,
+    /// Minimum and maximum corners of each region, in worldspace.
+    regions: Vec<(Coordinate, Coordinate)>,
+}
+
+impl Protection {
+    pub fn new(blocks: Vec<Block>, regions: Vec<(Coordinate, Coordinate)>) -> Self {
+        Self { blocks, regions }
+    }
+
+    /// Whether existing blocks must be read to check writes.
+    pub fn has_blocks(&self) -> bool {
+        !self.blocks.is_empty()
+    }
+
+    pub fn protects_block(&self, block: Block) -> bool {
+        self.blocks.contains(&block)
+    }
+
+    pub fn protects_location(&self, location: Coordinate) -> bool {
+        self.regions
+            .iter()
+            .any(|(min, max)| location.min(*min) == *min && location.max(*max) == *max)
+    }
+
+    /// Whether any protected region overlaps a cuboid, with corners in order.
+    pub fn overlaps(&self, min: Coordinate, max: Coordinate) -> bool {
+        self.regions.iter().any(|(region_min, region_max)| {
+            let (low, high) = (min.max(*region_min), max.min(*region_max));
+            low.min(high) == low
+        })
+    }
+}
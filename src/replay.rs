@@ -0,0 +1,153 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::time::Duration;
+
+use mcrs::{Block, Coordinate};
+
+use crate::backend::Backend;
+use crate::rules::Ant;
+use crate::text::{parse_block, parse_coordinate, parse_lines, parse_word};
+
+const HEADER: &str = "mcant-log 1";
+
+/// Log of every rule application and block write in a run, which can be played back without
+/// the schema.
+///
+/// Lines are:
+/// - `delay MS` once, after the header
+/// - `tick N` at the start of each tick
+/// - `step ID X Y Z RULESET RULE` for each rule applied, with the rule numbered from 1
+/// - `set X Y Z ID:MODIFIER` for each block written
+/// - `fill X1 Y1 Z1 X2 Y2 Z2 ID:MODIFIER` for each cuboid filled
+pub struct Recorder {
+    file: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &str, delay: Duration) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", HEADER)?;
+        writeln!(file, "delay {}", delay.as_millis())?;
+        Ok(Self { file })
+    }
+
+    /// Flushes the previous tick, so at most one tick is lost if the process is killed.
+    pub fn tick(&mut self, tick: u64) -> io::Result<()> {
+        self.file.flush()?;
+        writeln!(self.file, "tick {}", tick)
+    }
+
+    pub fn step(&mut self, ant: &Ant, rule_index: usize) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "step {} {} {} {} {} {}",
+            ant.id,
+            ant.position.x,
+            ant.position.y,
+            ant.position.z,
+            ant.ruleset,
+            rule_index + 1,
+        )
+    }
+
+    pub fn set(&mut self, location: Coordinate, block: Block) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "set {} {} {} {}:{}",
+            location.x, location.y, location.z, block.id, block.modifier
+        )
+    }
+
+    pub fn fill(&mut self, min: Coordinate, max: Coordinate, block: Block) -> io::Result<()> {
+        writeln!(
+            self.file,
+            "fill {} {} {} {} {} {} {}:{}",
+            min.x, min.y, min.z, max.x, max.y, max.z, block.id, block.modifier
+        )
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.file.flush();
+    }
+}
+
+/// Line of a log, after the header.
+enum Entry<'a> {
+    Delay(Duration),
+    Tick,
+    /// Rule application, for display.
+    Step(Vec<&'a str>),
+    Set(Coordinate, Block),
+    Fill(Coordinate, Coordinate, Block),
+}
+
+/// Apply the block writes of a log, waiting between ticks according to the recorded delay
+/// divided by `speed`.
+///
+/// Returns the number of ticks played.
+pub fn play(
+    mc: &mut dyn Backend,
+    path: &str,
+    speed: f64,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)?;
+    let lines: Vec<&str> = text.lines().collect();
+
+    if lines.first() != Some(&HEADER) {
+        return Err(format!("`{}` is not a replay log", path).into());
+    }
+
+    let entries = parse_lines(lines.into_iter().enumerate().skip(1), "log", parse_line)?;
+
+    let mut delay = Duration::ZERO;
+    let mut ticks = 0;
+
+    for entry in entries {
+        match entry {
+            Entry::Delay(recorded) => delay = recorded.div_f64(speed),
+            Entry::Tick => {
+                if ticks > 0 {
+                    // Ants are drawn twice per tick during a run
+                    std::thread::sleep(delay * 2);
+                }
+                ticks += 1;
+            }
+            Entry::Step(words) => println!("{}", words.join(" \t")),
+            Entry::Set(location, block) => mc.set_block(location, block)?,
+            Entry::Fill(min, max, block) => mc.set_blocks(min, max, block)?,
+        }
+    }
+
+    Ok(ticks)
+}
+
+fn parse_line(line: &str) -> Option<Entry<'_>> {
+    let mut words = line.split_whitespace();
+    let entry = match words.next()? {
+        "delay" => Entry::Delay(Duration::from_millis(parse_word(words.next())?)),
+        "tick" => {
+            parse_word::<u64>(words.next())?;
+            Entry::Tick
+        }
+        "step" => {
+            let words: Vec<_> = words.by_ref().collect();
+            if words.len() != 6 {
+                return None;
+            }
+            Entry::Step(words)
+        }
+        "set" => Entry::Set(parse_coordinate(&mut words)?, parse_block(words.next())?),
+        "fill" => Entry::Fill(
+            parse_coordinate(&mut words)?,
+            parse_coordinate(&mut words)?,
+            parse_block(words.next())?,
+        ),
+        _ => return None,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some(entry)
+}
@@ -6,6 +6,9 @@ use mcrs::{Block, Coordinate};
 pub struct Schema {
     pub ants: Vec<Ant>,
     pub rulesets: Vec<Ruleset>,
+    pub templates: Vec<Template>,
+    /// Initial values of global variables.
+    pub globals: Vec<(String, f64)>,
     pub properties: Properties,
 }
 
@@ -14,10 +17,16 @@ pub struct Ant {
     pub ruleset: String,
     pub offset: Coordinate,
     pub position: Coordinate,
+    /// Position which the ant was spawned at.
+    pub spawn_point: Coordinate,
     pub facing: Direction,
     pub state: State,
     pub halted: bool,
     pub id: usize,
+    /// Number of rules applied.
+    pub steps: u64,
+    /// Tick which the ant was spawned on.
+    pub born: u64,
 }
 
 impl Ant {
@@ -38,10 +47,154 @@ pub struct Rule {
     pub from_state: Vec<State>,
     pub from_block: Vec<Block>,
     pub from_facing: Vec<Direction>,
+    pub conditions: Vec<Condition>,
     pub to_state: State,
     pub to_block: Option<Block>,
     pub to_facing: Option<Direction>,
-    pub spawn: Option<Ant>,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug)]
+pub enum Action {
+    Spawn(Ant),
+    Stamp(String),
+    Fill(Block, Fill),
+    Deposit(String, f64),
+    /// Set global variable, at the end of the tick.
+    Assign(String, f64),
+    /// Set state of all ants in a ruleset, including halted ants, at the end of the tick.
+    Signal(String, State),
+}
+
+#[derive(Debug)]
+pub struct Condition {
+    pub quantity: Quantity,
+    pub comparison: Comparison,
+    pub value: f64,
+}
+
+#[derive(Clone, Debug)]
+pub enum Quantity {
+    /// Value of a field channel at the ant's position.
+    Scent(String),
+    /// Value of a global variable, as of the start of the tick.
+    Global(String),
+    /// Number of rules the ant has applied.
+    Steps,
+    /// Number of ticks since the ant was spawned.
+    Age,
+    /// Number of ticks since the run started.
+    Tick,
+    /// Coordinate of the ant on an axis.
+    Position(Relative, Axis),
+    /// Euclidean distance of the ant.
+    Distance(Relative),
+    /// Number of other live ants in the cell in front of the ant.
+    Ahead,
+    /// Number of other live ants in the same cell as the ant.
+    Occupied,
+    /// Whether the ant is on the boundary of the region, as 1 or 0. Always 0 without a region.
+    Edge,
+}
+
+impl Quantity {
+    /// Whether the value only depends on the position of the ant, so it cannot change while the
+    /// ant stays in one place.
+    pub fn is_positional(&self) -> bool {
+        matches!(
+            self,
+            Quantity::Position(..) | Quantity::Distance(_) | Quantity::Edge
+        )
+    }
+}
+
+/// Reference point for position quantities.
+#[derive(Clone, Copy, Debug)]
+pub enum Relative {
+    World,
+    /// Position which the ant was spawned at.
+    Spawn,
+    /// Player position when the run started.
+    Origin,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn get(self, coordinate: Coordinate) -> i32 {
+        match self {
+            Axis::X => coordinate.x,
+            Axis::Y => coordinate.y,
+            Axis::Z => coordinate.z,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    pub fn compare(self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterEqual => left >= right,
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Fill {
+    /// Line of blocks, starting at the ant, in any direction the ant faces.
+    Forward(i32),
+    /// Cuboid of `[width, height, depth]`, with the ant at the front-left corner.
+    Box([i32; 3]),
+}
+
+impl Fill {
+    /// Number of blocks in the region.
+    pub fn volume(self) -> u64 {
+        match self {
+            Fill::Forward(length) => length as u64,
+            Fill::Box(dimensions) => dimensions.iter().map(|&length| length as u64).product(),
+        }
+    }
+
+    /// Get opposite corners of the region, as worldspace offsets from the ant.
+    pub fn corners(self, facing: Direction) -> (Coordinate, Coordinate) {
+        match self {
+            Fill::Forward(length) => (
+                Coordinate::new(0, 0, 0),
+                Coordinate::from(facing.into_vec3().map(|value| value * (length - 1))),
+            ),
+            Fill::Box([width, height, depth]) => (
+                Coordinate::new(0, 0, 0),
+                facing.rotate([width - 1, height - 1, depth - 1]),
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Template {
+    pub name: String,
+    /// Cells as `[right, up, forward]` offsets from the ant.
+    pub blocks: Vec<([i32; 3], Block)>,
 }
 
 #[derive(Debug, Default)]
@@ -51,11 +204,147 @@ pub struct Properties {
     pub invisible: bool,
     pub cache_size: Option<u32>,
     pub cache_time: Option<Duration>,
+    /// Maximum number of cached chunks.
+    pub cache_limit: Option<usize>,
+    /// Number of blocks ahead of each ant to cache before each tick.
+    pub prefetch: Option<u32>,
+    pub decay: Option<f64>,
+    pub diffusion: Option<f64>,
+    pub collision: Option<Collision>,
+    pub update: Option<Update>,
+    pub halt: Option<Halt>,
+    pub cap_policy: Option<CapPolicy>,
+    /// Maximum number of live ants for each ruleset.
+    pub spawn_caps: Vec<(String, usize)>,
+    pub max_ticks: Option<u64>,
+    /// Maximum number of rules applied by each ant.
+    pub max_steps: Option<u64>,
+    /// Maximum wall-clock duration of the run.
+    pub timeout: Option<Duration>,
+    /// Maximum number of blocks changed.
+    pub max_changes: Option<u64>,
+    pub cycles: Option<Cycles>,
+    pub region: Option<Region>,
+    pub region_policy: Option<RegionPolicy>,
+    /// Blocks which are never overwritten.
+    pub protect: Vec<Block>,
+    /// Regions which are never written to.
+    pub protect_regions: Vec<Region>,
+    pub protect_policy: Option<ProtectPolicy>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub connect_timeout: Option<Duration>,
+    /// Number of attempts to reconnect after a connection failure.
+    pub reconnect: Option<u32>,
+    /// Whether block writes are queued and sent together at the end of each tick.
+    pub batch: Option<bool>,
 }
 
-pub type State = String;
+/// Bounding box which ants and block writes are confined to.
+#[derive(Clone, Copy, Debug)]
+pub struct Region {
+    /// Opposite corners, in any order. Both corners are inside the region.
+    pub corners: [Coordinate; 2],
+    /// Either [`Relative::World`] or [`Relative::Origin`].
+    pub relative: Relative,
+}
+
+impl Region {
+    /// Returns the minimum and maximum corners in worldspace.
+    pub fn resolve(&self, origin: Coordinate) -> (Coordinate, Coordinate) {
+        let offset = match self.relative {
+            Relative::Origin => origin,
+            _ => Coordinate::new(0, 0, 0),
+        };
+        let [corner_a, corner_b] = self.corners.map(|corner| corner + offset);
+        (corner_a.min(corner_b), corner_a.max(corner_b))
+    }
+}
+
+/// What happens when an ant tries to leave the region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegionPolicy {
+    /// The ant is halted.
+    Halt,
+    /// The ant turns around, without moving.
+    Bounce,
+}
+
+/// What happens when an ant is detected to be in a cycle.
+///
+/// Ants are not checked if their ruleset can be signalled, or has conditions other than position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cycles {
+    /// Cycles are not detected.
+    Off,
+    /// A warning is printed.
+    Warn,
+    /// The ant is halted.
+    Halt,
+}
+
+/// What happens when an ant tries to overwrite a protected block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProtectPolicy {
+    /// The write is skipped and logged.
+    Skip,
+    /// The write is skipped and logged, and the ant is halted.
+    Halt,
+}
 
+/// What happens when the number of ants exceeds the cap.
 #[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CapPolicy {
+    /// New ants are not spawned.
+    Reject,
+    /// Oldest halted ants are removed, then oldest live ants.
+    EvictHalted,
+    /// Oldest live ants are removed. Halted ants are left to be retired.
+    EvictOldest,
+    /// The whole run is stopped.
+    Stop,
+}
+
+/// What happens to the rest of the tick when an ant halts, for any reason: no matching rule, a
+/// limit or cycle, leaving the region, a collision or a protected block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Halt {
+    /// Remaining ants still step.
+    Continue,
+    /// Remaining ants skip the tick.
+    Interrupt,
+}
+
+/// How block changes are made visible to other ants.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Update {
+    /// Each ant reads and writes the world immediately, in order.
+    Sequential,
+    /// Every ant sees the world as of the previous tick, and all changes are applied together at
+    /// the end of the tick.
+    ///
+    /// This covers block writes, pheromone deposits, globals and signals. Occupancy conditions
+    /// and collisions use the positions of ants at the start of the tick, so an ant cannot move
+    /// into a cell which another ant is leaving in the same tick, unless collisions are allowed.
+    Synchronous,
+}
+
+/// What happens when an ant moves into a cell occupied by another live ant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collision {
+    /// Ants may share a cell.
+    Allow,
+    /// The moving ant stays where it is.
+    Block,
+    /// The moving ant stays where it is, and it and all ants in the occupied cell are halted.
+    Halt,
+    /// The moving ant is removed.
+    Merge,
+}
+
+pub type State = String;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     East,
     West,
@@ -76,4 +365,28 @@ impl Direction {
             Direction::Down => [0, -1, 0],
         }
     }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            Direction::South => Direction::North,
+            Direction::North => Direction::South,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    /// Convert a `[right, up, forward]` offset into a worldspace offset.
+    ///
+    /// Vertical directions are not rotated, and behave like [`Direction::East`].
+    pub fn rotate(self, [right, up, forward]: [i32; 3]) -> Coordinate {
+        let [fx, _, fz] = match self {
+            Direction::Up | Direction::Down => Direction::East.into_vec3(),
+            _ => self.into_vec3(),
+        };
+        // Right-hand side of horizontal direction
+        let [rx, rz] = [-fz, fx];
+        Coordinate::new(right * rx + forward * fx, up, right * rz + forward * fz)
+    }
 }
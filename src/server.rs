@@ -0,0 +1,95 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Address of the Minecraft server, and how to connect to it.
+pub struct Server {
+    host: String,
+    port: u16,
+    /// No timeout if `None`.
+    connect_timeout: Option<Duration>,
+    /// Number of attempts to reconnect after a connection failure, or `0` to never reconnect.
+    reconnect_attempts: u32,
+}
+
+impl Server {
+    const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    pub fn new(
+        host: String,
+        port: u16,
+        connect_timeout: Option<Duration>,
+        reconnect_attempts: u32,
+    ) -> Self {
+        Self {
+            host,
+            port,
+            connect_timeout,
+            reconnect_attempts,
+        }
+    }
+
+    pub fn connect(&self) -> io::Result<mcrs::Connection> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no address found for host `{}`", self.host),
+                )
+            })?;
+
+        match self.connect_timeout {
+            Some(timeout) => connect_with_timeout(address, timeout),
+            None => mcrs::Connection::with_address(address),
+        }
+    }
+
+    /// Retry connecting with exponential backoff, until the attempts run out.
+    pub fn reconnect(&self) -> io::Result<mcrs::Connection> {
+        let mut backoff = Self::INITIAL_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            std::thread::sleep(backoff);
+            match self.connect() {
+                Ok(connection) => return Ok(connection),
+                Err(error) if attempt >= self.reconnect_attempts => return Err(error),
+                Err(error) => {
+                    eprintln!(
+                        "reconnect attempt {} of {} failed: {}",
+                        attempt, self.reconnect_attempts, error
+                    );
+                }
+            }
+            attempt += 1;
+            backoff = (backoff * 2).min(Self::MAX_BACKOFF);
+        }
+    }
+
+    pub fn can_reconnect(&self) -> bool {
+        self.reconnect_attempts > 0
+    }
+}
+
+/// Whether an error may be fixed by reconnecting.
+pub fn is_connection_error(error: &mcrs::Error) -> bool {
+    matches!(error, mcrs::Error::IO(_) | mcrs::Error::UnexpectedEof)
+}
+
+/// `mcrs` does not expose its stream, so the connection is made on another thread, which is
+/// abandoned if it takes too long.
+fn connect_with_timeout(address: SocketAddr, timeout: Duration) -> io::Result<mcrs::Connection> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(mcrs::Connection::with_address(address));
+    });
+    receiver.recv_timeout(timeout).unwrap_or_else(|_| {
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("timed out connecting to {}", address),
+        ))
+    })
+}
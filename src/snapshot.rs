@@ -0,0 +1,202 @@
+use std::fmt;
+use std::fs;
+
+use mcrs::Coordinate;
+
+use crate::parse::Parser;
+use crate::rules::{Ant, Schema};
+use crate::text::{parse_coordinate, parse_word};
+
+const HEADER: &str = "mcant-snapshot 1";
+
+/// Complete state of a run between ticks, which can be saved and resumed.
+///
+/// Cycle detection histories are not included, so cycles are detected afresh after resuming.
+#[derive(Debug)]
+pub struct Snapshot {
+    /// Path of the schema file, so the snapshot can be resumed on its own.
+    pub schema_path: String,
+    pub schema_hash: u64,
+    pub tick: u64,
+    pub origin: Coordinate,
+    pub next_id: usize,
+    pub retired: usize,
+    pub dropped: usize,
+    pub changes: u64,
+    pub globals: Vec<(String, f64)>,
+    pub ants: Vec<Ant>,
+    /// Pheromone values, as channel, location and value.
+    pub scents: Vec<(String, Coordinate, f64)>,
+}
+
+impl Snapshot {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| format!("failed to read snapshot `{}`: {}", path, error))?;
+        Self::parse(&text)
+    }
+
+    /// Write to a temporary file first, so an interrupted save never leaves a partial snapshot.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let temporary = format!("{}.tmp", path);
+        fs::write(&temporary, self.to_string())
+            .and_then(|()| fs::rename(&temporary, path))
+            .map_err(|error| format!("failed to write snapshot `{}`: {}", path, error))
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(String::from("not a snapshot file"));
+        }
+
+        let mut snapshot = Snapshot {
+            schema_path: String::new(),
+            schema_hash: 0,
+            tick: 0,
+            origin: Coordinate::new(0, 0, 0),
+            next_id: 0,
+            retired: 0,
+            dropped: 0,
+            changes: 0,
+            globals: Vec::new(),
+            ants: Vec::new(),
+            scents: Vec::new(),
+        };
+
+        for (number, line) in lines {
+            let invalid = || format!("invalid snapshot line {}: `{}`", number + 1, line);
+
+            let Some((key, rest)) = line.split_once(' ') else {
+                return Err(invalid());
+            };
+            let mut words = rest.split_whitespace();
+
+            match key {
+                // Path may contain spaces
+                "schema" => snapshot.schema_path = rest.to_string(),
+                "hash" => {
+                    snapshot.schema_hash = (words.next())
+                        .and_then(|word| u64::from_str_radix(word, 16).ok())
+                        .ok_or_else(invalid)?;
+                }
+                "tick" => snapshot.tick = parse_word(words.next()).ok_or_else(invalid)?,
+                "origin" => snapshot.origin = parse_coordinate(&mut words).ok_or_else(invalid)?,
+                "next_id" => snapshot.next_id = parse_word(words.next()).ok_or_else(invalid)?,
+                "retired" => snapshot.retired = parse_word(words.next()).ok_or_else(invalid)?,
+                "dropped" => snapshot.dropped = parse_word(words.next()).ok_or_else(invalid)?,
+                "changes" => snapshot.changes = parse_word(words.next()).ok_or_else(invalid)?,
+                "global" => {
+                    let name = words.next().ok_or_else(invalid)?.to_string();
+                    let value = parse_word(words.next()).ok_or_else(invalid)?;
+                    snapshot.globals.push((name, value));
+                }
+                "ant" => {
+                    let ant = parse_ant(&mut words).ok_or_else(invalid)?;
+                    snapshot.ants.push(ant);
+                }
+                "scent" => {
+                    let channel = words.next().ok_or_else(invalid)?.to_string();
+                    let location = parse_coordinate(&mut words).ok_or_else(invalid)?;
+                    let value = parse_word(words.next()).ok_or_else(invalid)?;
+                    snapshot.scents.push((channel, location, value));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        if snapshot.schema_path.is_empty() {
+            return Err(String::from("snapshot is missing schema path"));
+        }
+        Ok(snapshot)
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "schema {}", self.schema_path)?;
+        writeln!(f, "hash {:016x}", self.schema_hash)?;
+        writeln!(f, "tick {}", self.tick)?;
+        writeln!(
+            f,
+            "origin {} {} {}",
+            self.origin.x, self.origin.y, self.origin.z
+        )?;
+        writeln!(f, "next_id {}", self.next_id)?;
+        writeln!(f, "retired {}", self.retired)?;
+        writeln!(f, "dropped {}", self.dropped)?;
+        writeln!(f, "changes {}", self.changes)?;
+        for (name, value) in &self.globals {
+            writeln!(f, "global {} {}", name, value)?;
+        }
+        for ant in &self.ants {
+            writeln!(
+                f,
+                "ant {} {} {} {} {} {} {} {} {:?} {} {} {} {}",
+                ant.id,
+                ant.ruleset,
+                ant.position.x,
+                ant.position.y,
+                ant.position.z,
+                ant.spawn_point.x,
+                ant.spawn_point.y,
+                ant.spawn_point.z,
+                ant.facing,
+                ant.state,
+                ant.halted,
+                ant.steps,
+                ant.born,
+            )?;
+        }
+        for (channel, location, value) in &self.scents {
+            writeln!(
+                f,
+                "scent {} {} {} {} {}",
+                channel, location.x, location.y, location.z, value
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Hash of the ants, rulesets and templates of a schema, ignoring formatting and properties.
+pub fn schema_hash(schema: &Schema) -> u64 {
+    // FNV-1a, which is stable between builds, unlike `DefaultHasher`
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let text = format!(
+        "{:?}{:?}{:?}",
+        schema.ants, schema.rulesets, schema.templates
+    );
+    text.bytes().fold(OFFSET, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+fn parse_ant<'a>(words: &mut impl Iterator<Item = &'a str>) -> Option<Ant> {
+    let id = parse_word(words.next())?;
+    let ruleset = words.next()?.to_string();
+    let position = parse_coordinate(words)?;
+    let spawn_point = parse_coordinate(words)?;
+    let facing = Parser::parse_direction(words.next()?)?;
+    let state = words.next()?.to_string();
+    let halted = parse_word(words.next())?;
+    let steps = parse_word(words.next())?;
+    let born = parse_word(words.next())?;
+
+    Some(Ant {
+        ruleset,
+        offset: Coordinate::new(0, 0, 0),
+        position,
+        spawn_point,
+        facing,
+        state,
+        halted,
+        id,
+        steps,
+        born,
+    })
+}
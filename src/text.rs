@@ -0,0 +1,52 @@
+use std::str::FromStr;
+
+use mcrs::{Block, Coordinate};
+
+/// Parse each line of a file which is written one line at a time.
+///
+/// The last line may be incomplete, if the process was killed while writing it, so it is ignored
+/// with a warning if invalid. `kind` names the file in messages.
+pub fn parse_lines<'a, T>(
+    lines: impl Iterator<Item = (usize, &'a str)>,
+    kind: &str,
+    mut parse: impl FnMut(&'a str) -> Option<T>,
+) -> Result<Vec<T>, String> {
+    let mut lines = lines.peekable();
+    let mut entries = Vec::new();
+    while let Some((number, line)) = lines.next() {
+        match parse(line) {
+            Some(entry) => entries.push(entry),
+            None if lines.peek().is_none() => {
+                eprintln!("ignoring incomplete last line of {}: `{}`", kind, line);
+            }
+            None => return Err(format!("invalid {} line {}: `{}`", kind, number + 1, line)),
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse a block written as `id:modifier`.
+pub fn parse_block(word: Option<&str>) -> Option<Block> {
+    let (id, modifier) = word?.split_once(':')?;
+    Some(Block::new(id.parse().ok()?, modifier.parse().ok()?))
+}
+
+/// Parse a coordinate written as three words.
+pub fn parse_coordinate<'a>(words: &mut impl Iterator<Item = &'a str>) -> Option<Coordinate> {
+    Some(Coordinate::new(
+        parse_word(words.next())?,
+        parse_word(words.next())?,
+        parse_word(words.next())?,
+    ))
+}
+
+pub fn parse_word<T: FromStr>(word: Option<&str>) -> Option<T> {
+    word?.parse().ok()
+}
+
+/// Parse a number given by the user, outside of a schema.
+pub fn parse_numeric<T: FromStr>(string: &str) -> Result<T, String> {
+    string
+        .parse()
+        .map_err(|_| format!("invalid number `{}`", string))
+}
@@ -1,63 +1,286 @@
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
-use mcrs::{Block, Coordinate, Size};
+use mcrs::{Block, Coordinate};
+
+use crate::backend::Backend;
+use crate::batch::WriteQueue;
+use crate::journal::Journal;
+use crate::overlay::Overlay;
+use crate::protect::Protection;
+use crate::replay::Recorder;
+use crate::server::{self, Server};
 
 pub struct World {
-    mc: mcrs::Connection,
+    mc: Box<dyn Backend>,
+    /// Used to reconnect after connection failures.
+    server: Option<Server>,
     cache: Cache,
+    /// Number of blocks written.
+    changes: u64,
+    journal: Option<Journal>,
+    recorder: Option<Recorder>,
+    /// Set for a dry run, so writes are not sent to the server.
+    overlay: Option<Overlay>,
+    protection: Protection,
+    /// Number of writes to protected blocks which were skipped.
+    violations: u64,
+    /// Set when writes are batched, until [`World::flush`] or a read from the server.
+    queue: Option<WriteQueue>,
+    /// Whether fills read the blocks they replace, so that unchanged blocks are not counted.
+    exact_changes: bool,
 }
 
+/// Grid-aligned chunks of blocks, with least-recently-used chunks evicted past a limit.
 struct Cache {
-    entries: HashMap<Coordinate, CacheEntry>,
-    next_clean: Instant,
-    /// Each side of cube are length `cache_size * 2 + 1`.
-    chunk_radius: u32,
-    /// Maximum lifetime for a cache entry.
+    /// By chunk index, which is the chunk's minimum corner divided by `chunk_size`.
+    chunks: HashMap<Coordinate, CacheChunk>,
+    /// Length of each side of a chunk, which is `cache_size * 2 + 1`.
+    chunk_size: i32,
+    /// Maximum lifetime for a chunk.
     max_lifetime: Duration,
+    /// Maximum number of chunks.
+    max_chunks: usize,
+    /// Incremented on every access, for finding the least-recently-used chunk.
+    clock: u64,
 }
 
-struct CacheEntry {
-    block: Block,
+struct CacheChunk {
+    /// Ordered by x, then y, then z.
+    blocks: Vec<Block>,
     expiration: Instant,
+    last_used: u64,
 }
 
 impl World {
-    pub fn new(mc: mcrs::Connection, cache_size: u32, cache_time: Duration) -> Self {
+    pub fn new(
+        mc: Box<dyn Backend>,
+        cache_size: u32,
+        cache_time: Duration,
+        cache_limit: usize,
+    ) -> Self {
         Self {
             mc,
-            cache: Cache::new(cache_size, cache_time),
+            server: None,
+            cache: Cache::new(cache_size, cache_time, cache_limit),
+            changes: 0,
+            journal: None,
+            recorder: None,
+            overlay: None,
+            protection: Protection::default(),
+            violations: 0,
+            queue: None,
+            exact_changes: false,
         }
     }
 
-    pub fn get_mc(&mut self) -> &mut mcrs::Connection {
-        &mut self.mc
+    /// Record the previous block of every write from now on.
+    pub fn set_journal(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    /// Record every write from now on.
+    pub fn set_recorder(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn recorder(&mut self) -> Option<&mut Recorder> {
+        self.recorder.as_mut()
+    }
+
+    /// Keep writes from now on in an overlay, while still reading the real world.
+    pub fn enable_dry_run(&mut self) {
+        self.overlay = Some(Overlay::default());
+    }
+
+    pub fn overlay(&self) -> Option<&Overlay> {
+        self.overlay.as_ref()
+    }
+
+    pub fn set_protection(&mut self, protection: Protection) {
+        self.protection = protection;
+    }
+
+    pub fn violations(&self) -> u64 {
+        self.violations
+    }
+
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    /// Count only blocks which fills actually change, at the cost of reading every filled block.
+    pub fn enable_exact_changes(&mut self) {
+        self.exact_changes = true;
+    }
+
+    /// Continue counting from a previous run.
+    pub fn set_changes(&mut self, changes: u64) {
+        self.changes = changes;
+    }
+
+    /// Reconnect to the server after connection failures, from now on.
+    pub fn set_server(&mut self, server: Server) {
+        self.server = Some(server);
+    }
+
+    /// Queue writes from now on, so they can be combined and sent together.
+    ///
+    /// Queued writes are laid over every block read from the server, so they are visible before
+    /// they are sent.
+    pub fn enable_batching(&mut self) {
+        self.queue = Some(WriteQueue::default());
+    }
+
+    /// Send all queued writes.
+    pub fn flush(&mut self) -> Result<(), mcrs::Error> {
+        let Some(queue) = self.queue.as_mut().filter(|queue| !queue.is_empty()) else {
+            return Ok(());
+        };
+        for (min, max, block) in queue.take_cuboids() {
+            if min == max {
+                self.with_connection(|mc| mc.set_block(min, block))?;
+            } else {
+                self.with_connection(|mc| mc.set_blocks(min, max, block))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a block from the server, or from the queue if it has not been sent yet.
+    fn read_block(&mut self, location: Coordinate) -> Result<Block, mcrs::Error> {
+        if let Some(block) = self.queue.as_ref().and_then(|queue| queue.get(location)) {
+            return Ok(block);
+        }
+        self.with_connection(|mc| mc.get_block(location))
+    }
+
+    /// Read every block in a cuboid from the server, replaced by any writes which have not been
+    /// sent yet.
+    fn read_blocks(
+        &mut self,
+        min: Coordinate,
+        max: Coordinate,
+    ) -> Result<Vec<(Coordinate, Block)>, mcrs::Error> {
+        let mut blocks = self.with_connection(|mc| mc.get_blocks(min, max))?;
+        if let Some(queue) = self.queue.as_ref().filter(|queue| !queue.is_empty()) {
+            for (location, block) in &mut blocks {
+                if let Some(queued) = queue.get(*location) {
+                    *block = queued;
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    pub fn shows_particles(&self) -> bool {
+        self.mc.shows_particles()
+    }
+
+    /// Run an operation on the connection.
+    ///
+    /// If the connection fails, the operation is retried once after reconnecting, and the cache is
+    /// cleared since the world may have changed in the meantime.
+    pub fn with_connection<T>(
+        &mut self,
+        mut operation: impl FnMut(&mut dyn Backend) -> Result<T, mcrs::Error>,
+    ) -> Result<T, mcrs::Error> {
+        match operation(self.mc.as_mut()) {
+            Err(error)
+                if server::is_connection_error(&error)
+                    && (self.server.as_ref()).is_some_and(Server::can_reconnect) =>
+            {
+                println!("====[ CONNECTION LOST: {} ]====", error);
+                let server = self.server.as_ref().unwrap();
+                self.mc = Box::new(server.reconnect()?);
+                self.cache.clear();
+                println!("====[ RECONNECTED ]====");
+                operation(self.mc.as_mut())
+            }
+            result => result,
+        }
     }
 
     pub fn get_block(&mut self, location: Coordinate) -> Result<Block, mcrs::Error> {
+        if let Some(block) = self
+            .overlay
+            .as_ref()
+            .and_then(|overlay| overlay.get(location))
+        {
+            return Ok(block);
+        }
+
         if !self.cache.enabled() {
-            return self.mc.get_block(location);
+            return self.read_block(location);
         }
 
         if let Some(block) = self.cache.get(location) {
             return Ok(block);
         }
 
-        self.cache.clean(location);
-
-        let (origin, bound) = self.cache.get_chunk(location);
-        let chunk = self.mc.get_blocks(origin, bound)?;
-        for entry in &chunk {
-            self.cache
-                .insert(location, entry.position_worldspace(), entry.block());
-        }
+        let (min, max) = self.cache.chunk_bounds(location);
+        let chunk = self.read_blocks(min, max)?;
+        self.cache.insert(&chunk);
 
         Ok(chunk
-            .get_worldspace(location)
+            .iter()
+            .find(|(position, _)| *position == location)
+            .map(|(_, block)| *block)
             .expect("block should be in chunk"))
     }
 
+    /// Load the chunks containing the locations into the cache, if they are not already cached.
+    ///
+    /// Missing chunks which are adjacent along the x axis are fetched in a single request.
+    pub fn prefetch(
+        &mut self,
+        locations: impl IntoIterator<Item = Coordinate>,
+    ) -> Result<(), mcrs::Error> {
+        if !self.cache.enabled() {
+            return Ok(());
+        }
+
+        let mut missing: Vec<[i32; 3]> = locations
+            .into_iter()
+            .map(|location| self.cache.chunk_index(location))
+            .filter(|index| !self.cache.contains(*index))
+            .collect();
+        missing.sort_by_key(|[x, y, z]| (*y, *z, *x));
+        missing.dedup();
+
+        let mut runs: Vec<([i32; 3], [i32; 3])> = Vec::new();
+        for index in missing {
+            match runs.last_mut() {
+                Some((_, end)) if end[1..] == index[1..] && end[0] + 1 == index[0] => *end = index,
+                _ => runs.push((index, index)),
+            }
+        }
+
+        for (start, end) in runs {
+            let (min, _) = self.cache.index_bounds(start);
+            let (_, max) = self.cache.index_bounds(end);
+            let chunk = self.read_blocks(min, max)?;
+            self.cache.insert(&chunk);
+        }
+        Ok(())
+    }
+
     pub fn set_block(&mut self, location: Coordinate, block: Block) -> Result<(), mcrs::Error> {
+        self.replace_block(location, block, None)
+    }
+
+    /// Whether the block being replaced must be known for each write.
+    fn needs_previous(&self) -> bool {
+        self.journal.is_some() || self.protection.has_blocks() || self.overlay.is_some()
+    }
+
+    /// `previous` is the block being replaced, if it is already known.
+    fn replace_block(
+        &mut self,
+        location: Coordinate,
+        block: Block,
+        previous: Option<Block>,
+    ) -> Result<(), mcrs::Error> {
         if self
             .cache
             .get(location)
@@ -66,47 +289,231 @@ impl World {
             return Ok(());
         }
 
-        self.cache.clean(location);
-        self.cache.insert(location, location, block);
-        self.mc.set_block(location, block)
+        if self.protection.protects_location(location) {
+            self.skip_protected(location, None);
+            return Ok(());
+        }
+
+        let previous = match previous {
+            Some(previous) => Some(previous),
+            None if self.needs_previous() => Some(self.get_block(location)?),
+            None => None,
+        };
+        if let Some(previous) = previous {
+            if previous == block {
+                return Ok(());
+            }
+            if self.protection.protects_block(previous) {
+                self.skip_protected(location, Some(previous));
+                return Ok(());
+            }
+            if let Some(journal) = &mut self.journal {
+                journal
+                    .record(location, previous)
+                    .map_err(mcrs::Error::IO)?;
+            }
+        }
+
+        self.cache.set(location, block);
+        self.changes += 1;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.set(location, block).map_err(mcrs::Error::IO)?;
+        }
+        if let Some(overlay) = &mut self.overlay {
+            let original = previous.expect("previous block should be read for dry run");
+            overlay.set(location, original, block);
+            return Ok(());
+        }
+        if let Some(queue) = &mut self.queue {
+            queue.push(location, block);
+            return Ok(());
+        }
+        self.with_connection(|mc| mc.set_block(location, block))
+    }
+
+    /// Read every block in a cuboid, with corners in order, from the cache if all of it is cached.
+    fn get_blocks(
+        &mut self,
+        min: Coordinate,
+        max: Coordinate,
+    ) -> Result<Vec<(Coordinate, Block)>, mcrs::Error> {
+        let cached: Option<Vec<_>> = cells(min, max)
+            .map(|location| Some((location, self.cache.get(location)?)))
+            .collect();
+        let mut blocks = match cached {
+            Some(blocks) => blocks,
+            None => self.read_blocks(min, max)?,
+        };
+
+        if let Some(overlay) = &self.overlay {
+            for (location, block) in &mut blocks {
+                if let Some(written) = overlay.get(*location) {
+                    *block = written;
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    fn skip_protected(&mut self, location: Coordinate, existing: Option<Block>) {
+        self.violations += 1;
+        match existing {
+            Some(block) => println!(
+                "====[ PROTECTED {} at {} ]====",
+                block.get_name().unwrap_or("[unknown]"),
+                location
+            ),
+            None => println!("====[ PROTECTED region at {} ]====", location),
+        }
+    }
+
+    /// If the replaced blocks must be known, they are read together first.
+    pub fn set_blocks(
+        &mut self,
+        blocks: impl IntoIterator<Item = (Coordinate, Block)>,
+    ) -> Result<(), mcrs::Error> {
+        let blocks: Vec<(Coordinate, Block)> = blocks.into_iter().collect();
+        let Some((first, _)) = blocks.first() else {
+            return Ok(());
+        };
+
+        let mut previous = Vec::new();
+        if self.needs_previous() && blocks.len() > 1 {
+            let (min, max) = (blocks.iter()).fold((*first, *first), |(min, max), (location, _)| {
+                (min.min(*location), max.max(*location))
+            });
+            previous = self.get_blocks(min, max)?;
+        }
+        self.replace_blocks(blocks, previous)
+    }
+
+    /// `previous` has the blocks being replaced, which may be empty or cover other locations.
+    fn replace_blocks(
+        &mut self,
+        blocks: Vec<(Coordinate, Block)>,
+        previous: Vec<(Coordinate, Block)>,
+    ) -> Result<(), mcrs::Error> {
+        let mut previous: HashMap<Coordinate, Block> = previous.into_iter().collect();
+        for (location, block) in blocks {
+            // Later writes to the same location replace this one
+            let replaced = if previous.is_empty() {
+                None
+            } else {
+                previous.insert(location, block)
+            };
+            self.replace_block(location, block, replaced)?;
+        }
+        Ok(())
+    }
+
+    /// Set every block in the cuboid between two corners (in any order), in a single request.
+    ///
+    /// Falls back to setting each block separately if the cuboid contains protected blocks. Only
+    /// blocks which differ count as changes.
+    pub fn fill(
+        &mut self,
+        corner_a: Coordinate,
+        corner_b: Coordinate,
+        block: Block,
+    ) -> Result<(), mcrs::Error> {
+        let (min, max) = (corner_a.min(corner_b), corner_a.max(corner_b));
+
+        if self.protection.overlaps(min, max) {
+            return self.set_blocks(cells(min, max).map(|location| (location, block)));
+        }
+
+        let changes = if self.needs_previous() || self.exact_changes {
+            let previous = self.get_blocks(min, max)?;
+            if (previous.iter()).any(|(_, previous)| self.protection.protects_block(*previous)) {
+                let blocks = cells(min, max).map(|location| (location, block)).collect();
+                return self.replace_blocks(blocks, previous);
+            }
+
+            let changed: Vec<(Coordinate, Block)> = (previous.into_iter())
+                .filter(|(_, previous)| *previous != block)
+                .collect();
+            if changed.is_empty() {
+                return Ok(());
+            }
+
+            if let Some(journal) = &mut self.journal {
+                for (location, previous) in &changed {
+                    journal
+                        .record(*location, *previous)
+                        .map_err(mcrs::Error::IO)?;
+                }
+            }
+            if let Some(overlay) = &mut self.overlay {
+                for (location, original) in &changed {
+                    overlay.set(*location, *original, block);
+                }
+            }
+            changed.len() as u64
+        } else {
+            let size = min.size_between(max);
+            size.x as u64 * size.y as u64 * size.z as u64
+        };
+
+        self.cache.fill(min, max, block);
+        self.changes += changes;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.fill(min, max, block).map_err(mcrs::Error::IO)?;
+        }
+        if self.overlay.is_some() {
+            return Ok(());
+        }
+        // Queued writes must not be applied over the fill
+        if let Some(queue) = &mut self.queue {
+            queue.remove_within(min, max);
+        }
+        self.with_connection(|mc| mc.set_blocks(min, max, block))
     }
 }
 
-impl Cache {
-    const CACHE_COOLDOWN: Duration = Duration::from_secs(4);
-    const MAX_DISTANCE: u32 = 16;
+/// Every location in a cuboid, with corners in order.
+fn cells(min: Coordinate, max: Coordinate) -> impl Iterator<Item = Coordinate> {
+    (min.x..=max.x).flat_map(move |x| {
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| Coordinate::new(x, y, z)))
+    })
+}
 
-    pub fn new(chunk_radius: u32, max_lifetime: Duration) -> Self {
+impl Cache {
+    pub fn new(cache_size: u32, max_lifetime: Duration, max_chunks: usize) -> Self {
         Self {
-            entries: HashMap::new(),
-            next_clean: Instant::now() + Self::CACHE_COOLDOWN,
-            chunk_radius,
+            chunks: HashMap::new(),
+            chunk_size: cache_size as i32 * 2 + 1,
             max_lifetime,
+            max_chunks,
+            clock: 0,
         }
     }
 
-    pub fn get_chunk(&self, location: Coordinate) -> (Coordinate, Coordinate) {
-        debug_assert!(self.enabled());
-        let size_half = Size::new(self.chunk_radius, self.chunk_radius, self.chunk_radius);
-        (location - size_half, location + size_half)
+    pub fn enabled(&self) -> bool {
+        self.chunk_size > 1 && self.max_lifetime.as_millis() > 0 && self.max_chunks > 0
     }
 
-    /// Call before inserting cache.
-    pub fn clean(&mut self, origin: Coordinate) {
-        if !self.enabled() {
-            return;
-        }
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
 
-        let now = Instant::now();
-        if now < self.next_clean {
-            return;
-        }
+    /// Minimum and maximum corners of the chunk containing a location.
+    pub fn chunk_bounds(&self, location: Coordinate) -> (Coordinate, Coordinate) {
+        self.index_bounds(self.chunk_index(location))
+    }
 
-        self.entries.retain(|location, entry| {
-            now <= entry.expiration && manhattan_distance(origin, *location) <= Self::MAX_DISTANCE
-        });
+    /// Minimum and maximum corners of a chunk.
+    fn index_bounds(&self, index: [i32; 3]) -> (Coordinate, Coordinate) {
+        debug_assert!(self.enabled());
+        let min = index.map(|index| index * self.chunk_size);
+        let max = min.map(|min| min + self.chunk_size - 1);
+        (min.into(), max.into())
+    }
 
-        self.next_clean = now + Self::CACHE_COOLDOWN;
+    /// Whether a chunk is cached and not expired, without counting as a use.
+    fn contains(&self, index: [i32; 3]) -> bool {
+        self.chunks
+            .get(&index.into())
+            .is_some_and(|chunk| Instant::now() <= chunk.expiration)
     }
 
     pub fn get(&mut self, location: Coordinate) -> Option<Block> {
@@ -114,40 +521,328 @@ impl Cache {
             return None;
         }
 
-        let entry = self.entries.get(&location)?;
-        if Instant::now() > entry.expiration {
-            self.entries.remove(&location);
+        let index = self.chunk_index(location);
+        let block_index = self.block_index(location);
+        let chunk = self.chunks.get_mut(&index.into())?;
+        if Instant::now() > chunk.expiration {
+            self.chunks.remove(&index.into());
             return None;
         }
-        Some(entry.block)
+
+        self.clock += 1;
+        chunk.last_used = self.clock;
+        Some(chunk.blocks[block_index])
     }
 
-    pub fn insert(&mut self, origin: Coordinate, location: Coordinate, block: Block) {
+    /// Cache every chunk in a cuboid, which must be aligned to chunk boundaries, as from
+    /// [`Cache::chunk_bounds`].
+    pub fn insert(&mut self, cuboid: &[(Coordinate, Block)]) {
         if !self.enabled() {
             return;
         }
 
-        self.entries.insert(
-            location,
-            CacheEntry {
-                block,
-                expiration: self.calculate_expiration(origin, location),
-            },
-        );
+        let mut chunks: HashMap<[i32; 3], Vec<Block>> = HashMap::new();
+        for (location, block) in cuboid {
+            let blocks = (chunks.entry(self.chunk_index(*location)))
+                .or_insert_with(|| vec![Block::AIR; (self.chunk_size as usize).pow(3)]);
+            blocks[self.block_index(*location)] = *block;
+        }
+
+        for (index, blocks) in chunks {
+            if self.chunks.len() >= self.max_chunks {
+                self.evict();
+            }
+            self.clock += 1;
+            self.chunks.insert(
+                index.into(),
+                CacheChunk {
+                    blocks,
+                    expiration: Instant::now() + self.max_lifetime,
+                    last_used: self.clock,
+                },
+            );
+        }
     }
 
-    pub fn enabled(&self) -> bool {
-        self.chunk_radius > 0 && self.max_lifetime.as_millis() > 0
+    /// Update a block, if its chunk is cached.
+    pub fn set(&mut self, location: Coordinate, block: Block) {
+        let index = self.block_index(location);
+        if let Some(chunk) = self.chunks.get_mut(&self.chunk_index(location).into()) {
+            chunk.blocks[index] = block;
+        }
+    }
+
+    /// Update every block in a cuboid which is in a cached chunk.
+    pub fn fill(&mut self, min: Coordinate, max: Coordinate, block: Block) {
+        let indices: Vec<Coordinate> = self.chunks.keys().copied().collect();
+        for index in indices {
+            let chunk_min =
+                Coordinate::from([index.x, index.y, index.z].map(|i| i * self.chunk_size));
+            let chunk_max = chunk_min + [self.chunk_size - 1; 3];
+            let (low, high) = (min.max(chunk_min), max.min(chunk_max));
+            if low.min(high) != low {
+                continue;
+            }
+            for x in low.x..=high.x {
+                for y in low.y..=high.y {
+                    for z in low.z..=high.z {
+                        self.set(Coordinate::new(x, y, z), block);
+                    }
+                }
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        let oldest = (self.chunks.iter())
+            .min_by_key(|(_, chunk)| chunk.last_used)
+            .map(|(index, _)| *index);
+        if let Some(index) = oldest {
+            self.chunks.remove(&index);
+        }
+    }
+
+    fn chunk_index(&self, location: Coordinate) -> [i32; 3] {
+        [location.x, location.y, location.z].map(|value| value.div_euclid(self.chunk_size))
     }
 
-    fn calculate_expiration(&self, origin: Coordinate, location: Coordinate) -> Instant {
-        let dist = manhattan_distance(origin, location);
-        let max_dist = self.chunk_radius * 3;
-        let lifetime = (self.max_lifetime * (max_dist - dist)) / max_dist;
-        Instant::now() + lifetime
+    fn block_index(&self, location: Coordinate) -> usize {
+        let [x, y, z] = [location.x, location.y, location.z]
+            .map(|value| value.rem_euclid(self.chunk_size) as usize);
+        let size = self.chunk_size as usize;
+        (x * size + y) * size + z
     }
 }
 
-fn manhattan_distance(a: Coordinate, b: Coordinate) -> u32 {
-    a.x.abs_diff(b.x) + a.y.abs_diff(b.y) + a.z.abs_diff(b.z)
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::fmt;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::backend::Memory;
+
+    /// Offline world which counts requests.
+    #[derive(Default)]
+    struct Counting {
+        memory: Memory,
+        counts: Rc<Counts>,
+    }
+
+    #[derive(Default)]
+    struct Counts {
+        reads: Cell<usize>,
+        writes: Cell<usize>,
+    }
+
+    impl Counts {
+        fn reset(&self) {
+            self.reads.set(0);
+            self.writes.set(0);
+        }
+    }
+
+    impl Backend for Counting {
+        fn get_player_position(&mut self) -> Result<Coordinate, mcrs::Error> {
+            self.memory.get_player_position()
+        }
+
+        fn get_block(&mut self, location: Coordinate) -> Result<Block, mcrs::Error> {
+            self.counts.reads.set(self.counts.reads.get() + 1);
+            self.memory.get_block(location)
+        }
+
+        fn get_blocks(
+            &mut self,
+            corner_a: Coordinate,
+            corner_b: Coordinate,
+        ) -> Result<Vec<(Coordinate, Block)>, mcrs::Error> {
+            self.counts.reads.set(self.counts.reads.get() + 1);
+            self.memory.get_blocks(corner_a, corner_b)
+        }
+
+        fn set_block(&mut self, location: Coordinate, block: Block) -> Result<(), mcrs::Error> {
+            self.counts.writes.set(self.counts.writes.get() + 1);
+            self.memory.set_block(location, block)
+        }
+
+        fn set_blocks(
+            &mut self,
+            corner_a: Coordinate,
+            corner_b: Coordinate,
+            block: Block,
+        ) -> Result<(), mcrs::Error> {
+            self.counts.writes.set(self.counts.writes.get() + 1);
+            self.memory.set_blocks(corner_a, corner_b, block)
+        }
+
+        fn do_command(&mut self, command: fmt::Arguments) -> Result<(), mcrs::Error> {
+            self.memory.do_command(command)
+        }
+
+        fn shows_particles(&self) -> bool {
+            self.memory.shows_particles()
+        }
+    }
+
+    fn world(cache_size: u32) -> World {
+        World::new(
+            Box::new(Memory::default()),
+            cache_size,
+            Duration::from_secs(60),
+            16,
+        )
+    }
+
+    /// Returns the number of requests so far, which is shared with the world.
+    fn counting_world(cache_size: u32) -> (World, Rc<Counts>) {
+        let backend = Counting::default();
+        let counts = Rc::clone(&backend.counts);
+        let world = World::new(Box::new(backend), cache_size, Duration::from_secs(60), 16);
+        (world, counts)
+    }
+
+    fn row(length: i32, block: Block) -> impl Iterator<Item = (Coordinate, Block)> {
+        (0..length).map(move |x| (Coordinate::new(x, 0, 0), block))
+    }
+
+    #[test]
+    fn protected_blocks_are_read_once_per_stamp() {
+        for cache_size in [0, 1] {
+            let (mut world, counts) = counting_world(cache_size);
+            world
+                .set_block(Coordinate::new(2, 0, 0), Block::STONE)
+                .unwrap();
+            world.set_protection(Protection::new(vec![Block::STONE], Vec::new()));
+            counts.reset();
+
+            world.set_blocks(row(5, Block::DIRT)).unwrap();
+            assert_eq!(counts.reads.get(), 1);
+            assert_eq!(world.violations(), 1);
+            assert_eq!(
+                world.get_block(Coordinate::new(2, 0, 0)).unwrap(),
+                Block::STONE
+            );
+            assert_eq!(
+                world.get_block(Coordinate::new(3, 0, 0)).unwrap(),
+                Block::DIRT
+            );
+
+            // Written blocks are known without reading again, if they are cached
+            counts.reset();
+            world.set_blocks(row(5, Block::GLASS)).unwrap();
+            assert_eq!(counts.reads.get(), if cache_size == 0 { 1 } else { 0 });
+            assert_eq!(world.violations(), 2);
+        }
+    }
+
+    #[test]
+    fn fill_counts_changed_blocks() {
+        for cache_size in [0, 1] {
+            let mut world = world(cache_size);
+            world.enable_exact_changes();
+            world
+                .fill(
+                    Coordinate::new(0, 0, 0),
+                    Coordinate::new(2, 0, 0),
+                    Block::STONE,
+                )
+                .unwrap();
+            assert_eq!(world.changes(), 3);
+
+            world
+                .fill(
+                    Coordinate::new(4, 0, 0),
+                    Coordinate::new(0, 0, 0),
+                    Block::STONE,
+                )
+                .unwrap();
+            assert_eq!(world.changes(), 5, "only new blocks should count");
+
+            world
+                .fill(
+                    Coordinate::new(0, 0, 0),
+                    Coordinate::new(4, 0, 0),
+                    Block::STONE,
+                )
+                .unwrap();
+            assert_eq!(world.changes(), 5);
+            assert_eq!(
+                world.get_block(Coordinate::new(3, 0, 0)).unwrap(),
+                Block::STONE
+            );
+        }
+    }
+
+    #[test]
+    fn queued_writes_are_coalesced_despite_reads() {
+        for cache_size in [0, 1] {
+            let (mut world, counts) = counting_world(cache_size);
+            world.enable_batching();
+            world.set_protection(Protection::new(vec![Block::STONE], Vec::new()));
+
+            for (location, block) in row(5, Block::DIRT) {
+                world.set_block(location, block).unwrap();
+            }
+            assert_eq!(counts.writes.get(), 0, "writes should be queued");
+            assert_eq!(
+                world.get_block(Coordinate::new(4, 0, 0)).unwrap(),
+                Block::DIRT
+            );
+            assert_eq!(
+                world.get_block(Coordinate::new(5, 0, 0)).unwrap(),
+                Block::AIR
+            );
+
+            world.flush().unwrap();
+            assert_eq!(counts.writes.get(), 1);
+            assert_eq!(world.changes(), 5);
+        }
+    }
+
+    #[test]
+    fn fill_replaces_queued_writes() {
+        let (mut world, counts) = counting_world(0);
+        world.enable_batching();
+        world
+            .set_block(Coordinate::new(1, 0, 0), Block::GLASS)
+            .unwrap();
+        world
+            .set_block(Coordinate::new(9, 0, 0), Block::GLASS)
+            .unwrap();
+        world
+            .fill(
+                Coordinate::new(0, 0, 0),
+                Coordinate::new(2, 0, 0),
+                Block::DIRT,
+            )
+            .unwrap();
+        world.flush().unwrap();
+
+        assert_eq!(counts.writes.get(), 2);
+        assert_eq!(
+            world.get_block(Coordinate::new(1, 0, 0)).unwrap(),
+            Block::DIRT
+        );
+        assert_eq!(
+            world.get_block(Coordinate::new(9, 0, 0)).unwrap(),
+            Block::GLASS
+        );
+    }
+
+    #[test]
+    fn fill_reads_nothing_unless_needed() {
+        let (mut world, counts) = counting_world(1);
+        world
+            .fill(
+                Coordinate::new(0, 0, 0),
+                Coordinate::new(2, 2, 2),
+                Block::STONE,
+            )
+            .unwrap();
+
+        assert_eq!(counts.reads.get(), 0);
+        assert_eq!(world.changes(), 27);
+    }
 }